@@ -0,0 +1,130 @@
+//! Adapter sequences masked out of reads before their k-mers are counted
+
+use std::ops::Range;
+use std::path::Path;
+
+use memchr::memmem::Finder;
+
+use crate::open_reader;
+
+/// Adapter sequences looked up verbatim in every read
+#[derive(Clone, Debug)]
+pub struct Adapters {
+    finders: Vec<Finder<'static>>,
+    /// Whether reads keep their case, so they are uppercased to look the adapters up
+    case_sensitive: bool,
+}
+
+impl Adapters {
+    /// Adapters from their sequences, matched regardless of case: they are uppercased, and
+    /// when counting is `case_sensitive`, so reads keep their case, a read is uppercased to
+    /// look them up, so soft-masked adapter copies are masked too. Empty sequences are dropped.
+    pub fn new(sequences: impl IntoIterator<Item = Vec<u8>>, case_sensitive: bool) -> Self {
+        let finders = sequences
+            .into_iter()
+            .filter(|seq| !seq.is_empty())
+            .map(|seq| Finder::new(&seq.to_ascii_uppercase()).into_owned())
+            .collect();
+        Adapters { finders, case_sensitive }
+    }
+
+    /// Read the adapters from a FASTA or FASTQ file, compressed or not, as inputs are read,
+    /// for reads read with `case_sensitive` (see [`new`](Self::new))
+    pub fn open(path: &Path, case_sensitive: bool) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut reader = open_reader(path)?;
+        let mut sequences = Vec::new();
+        while let Some(record) = reader.next_record(false) {
+            sequences.push(record?.seq);
+        }
+        Ok(Adapters::new(sequences, case_sensitive))
+    }
+
+    pub fn len(&self) -> usize {
+        self.finders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.finders.is_empty()
+    }
+
+    /// Stretches of `seq` covered by an exact match of some adapter, sorted and disjoint.
+    /// Overlapping matches, of one adapter or of several, merge into one stretch.
+    pub fn matches(&self, seq: &[u8]) -> Vec<Range<usize>> {
+        let uppercased;
+        let seq = match self.case_sensitive {
+            true => {
+                uppercased = seq.to_ascii_uppercase();
+                &uppercased[..]
+            }
+            false => seq,
+        };
+        let mut matches = Vec::new();
+        for finder in &self.finders {
+            let len = finder.needle().len();
+            let mut from = 0;
+            while let Some(at) = finder.find(&seq[from..]) {
+                matches.push(from + at..from + at + len);
+                from += at + 1;
+            }
+        }
+        matches.sort_unstable_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(matches.len());
+        for range in matches {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+
+    /// Cut the adapter matches of `seq` out of `runs`, sorted disjoint stretches of it
+    /// (such as [`SeqRecord::quality_ranges`](crate::SeqRecord::quality_ranges)), so no
+    /// counted window overlaps an adapter. Returns the bases of `seq` matching an adapter.
+    pub fn mask(&self, seq: &[u8], runs: &mut Vec<Range<usize>>) -> u64 {
+        let matches = self.matches(seq);
+        if matches.is_empty() {
+            return 0;
+        }
+        let mut kept = Vec::with_capacity(runs.len() + matches.len());
+        let mut next = 0;
+        for run in runs.drain(..) {
+            let mut start = run.start;
+            // Matches ending before the run cannot cut the runs after it either
+            while matches.get(next).is_some_and(|adapter| adapter.end <= start) {
+                next += 1;
+            }
+            for adapter in matches[next..].iter().take_while(|adapter| adapter.start < run.end) {
+                if adapter.start > start {
+                    kept.push(start..adapter.start);
+                }
+                start = start.max(adapter.end);
+            }
+            if start < run.end {
+                kept.push(start..run.end);
+            }
+        }
+        *runs = kept;
+        matches.iter().map(|range| range.len() as u64).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowercase_adapters_are_matched_in_either_case_mode() {
+        let adapters = [b"agatc".to_vec()];
+        // Reads are uppercased as they are read unless counting is case-sensitive
+        let uppercased = Adapters::new(adapters.clone(), false);
+        assert_eq!(uppercased.matches(b"TTAGATCTT"), vec![2..7]);
+        let case_sensitive = Adapters::new(adapters, true);
+        assert_eq!(case_sensitive.matches(b"TTagatcTT"), vec![2..7]);
+        assert_eq!(case_sensitive.matches(b"TTAGATCTT"), vec![2..7]);
+        assert_eq!(case_sensitive.matches(b"TTaGaTcTT"), vec![2..7]);
+        let mut runs = vec![0..4, 5..9];
+        assert_eq!(case_sensitive.mask(b"ttagatctt", &mut runs), 5);
+        assert_eq!(runs, vec![0..2, 7..9]);
+    }
+}
@@ -0,0 +1,1876 @@
+use rayon::prelude::*;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+use std::hash::{BuildHasher, Hash};
+
+/// Fast hash map
+use dashmap::DashMap;
+use rustc_hash::{FxBuildHasher, FxHashMap};
+
+mod masking;
+mod persist;
+
+use crate::nthash::NtHash;
+use masking::{DinucleotideEntropy, GcWindow, HomopolymerRuns};
+use crate::sketch::{mix64, murmur3_64, CountMinSketch, HeavyHitter, HeavyHitters, HyperLogLog, MinHash, MINHASH_SEED};
+use crate::{canonicalize, is_amino_acid, is_palindrome, is_unambiguous, strand_order};
+
+/// Largest k whose 2-bit encoding fits in a `u64`
+pub const MAX_PACKED_K: usize = 32;
+
+/// Largest packed key length counted in a dense array of all `4^k` keys
+/// (32 MiB of counts at the limit) instead of a hash map
+pub const MAX_DENSE_K: usize = 12;
+
+/// Default number of observations after which a k-mer is solid
+pub const DEFAULT_MIN_COUNT: u16 = 2;
+
+/// Rows of the Count-Min sketch used by bounded-memory counting
+const COUNT_MIN_DEPTH: usize = 4;
+
+/// Most frequent k-mers remembered by bounded-memory counting
+const HEAVY_HITTERS: usize = 16;
+
+/// Precision of the distinct-count sketch kept alongside the Count-Min sketch
+const COUNT_MIN_HLL_PRECISION: u8 = 14;
+
+/// 2-bit code of an unambiguous base (A=0, C=1, G=2, T/U=3), so that integer
+/// order of packed k-mers matches lexicographic order of their bytes
+fn base_code(base: u8) -> u64 {
+    match base {
+        b'A' | b'a' => 0,
+        b'C' | b'c' => 1,
+        b'G' | b'g' => 2,
+        _ => 3,
+    }
+}
+
+/// Bytes of a 2-bit packed k-mer
+fn unpack_kmer(packed: u64, k: usize, rna: bool) -> Vec<u8> {
+    let mut kmer = Vec::with_capacity(k);
+    unpack_kmer_into(packed, k, rna, &mut kmer);
+    kmer
+}
+
+/// Replace the contents of `out` with the bytes of a 2-bit packed k-mer
+fn unpack_kmer_into(packed: u64, k: usize, rna: bool, out: &mut Vec<u8>) {
+    let t = if rna { b'U' } else { b'T' };
+    out.clear();
+    out.extend((0..k).rev().map(|i| [b'A', b'C', b'G', t][((packed >> (2 * i)) & 3) as usize]));
+}
+
+/// Sketches replacing the exact map in bounded-memory mode
+struct CountMinState {
+    sketch: CountMinSketch,
+    distinct: HyperLogLog,
+    heavy: HeavyHitters,
+}
+
+/// Bits per count in the table of every possible key used for short k-mers, see
+/// [`KmerCounter::set_count_width`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CountWidth {
+    /// Counts stop at 255
+    U8,
+    /// Counts stop at 65535
+    U16,
+}
+
+impl CountWidth {
+    /// The narrowest width holding counts up to `saturate_at`
+    pub fn for_counts(saturate_at: u16) -> Self {
+        if saturate_at <= u8::MAX as u16 { CountWidth::U8 } else { CountWidth::U16 }
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            CountWidth::U8 => 8,
+            CountWidth::U16 => 16,
+        }
+    }
+
+    /// Highest count stored at this width
+    pub fn max_count(self) -> u16 {
+        match self {
+            CountWidth::U8 => u8::MAX as u16,
+            CountWidth::U16 => u16::MAX,
+        }
+    }
+}
+
+/// Cell of the dense table, of one of the [`CountWidth`]s
+trait Cell: Copy + Into<u16> + Send + Sync {
+    const MAX: u16;
+
+    /// `count`, which is at most `MAX`
+    fn from_count(count: u16) -> Self;
+}
+
+impl Cell for u8 {
+    const MAX: u16 = u8::MAX as u16;
+
+    fn from_count(count: u16) -> Self {
+        count as u8
+    }
+}
+
+impl Cell for u16 {
+    const MAX: u16 = u16::MAX;
+
+    fn from_count(count: u16) -> Self {
+        count
+    }
+}
+
+/// Cells of the dense table, in its count width
+enum DenseCells {
+    Narrow(Vec<u8>),
+    Wide(Vec<u16>),
+}
+
+/// Counts of every possible 2-bit packed key, indexed by the key itself
+struct DenseCounts {
+    cells: DenseCells,
+    /// Keys with a non-zero count
+    distinct: usize,
+}
+
+/// Storage for canonical k-mers with their counts, saturating at the solid threshold.
+/// Each map variant holds one map per shard; a key always lives in the same shard.
+enum KmerMap {
+    /// Packed keys of at most `MAX_DENSE_K` bases, where no hashing is needed at all
+    Dense(DenseCounts),
+    /// 2-bit packed keys, used when k <= 32 and every counted base is A/C/G/T
+    Packed(Vec<FxHashMap<u64, u16>>),
+    /// Raw bytes, for large k or when ambiguous/lowercase bases must be kept apart
+    Bytes(Vec<FxHashMap<Vec<u8>, u16>>),
+    /// 2-bit packed keys in one map that worker threads update concurrently, see
+    /// `set_concurrent_map`; DashMap does the sharding, behind its own locks
+    ConcurrentPacked(SharedMap<u64>),
+    /// Raw bytes in one concurrently updated map
+    ConcurrentBytes(SharedMap<Vec<u8>>),
+    /// No keys kept at all, only a HyperLogLog sketch of their hashes
+    Estimate(HyperLogLog),
+    /// Bounded memory: Count-Min counts for solidity, HyperLogLog for distinct
+    CountMin(Box<CountMinState>),
+}
+
+/// Map shared between counting threads
+type SharedMap<K> = DashMap<K, u16, FxBuildHasher>;
+
+impl KmerMap {
+    fn new(packed: bool, key_len: usize, shards: usize, concurrent: bool, width: CountWidth) -> Self {
+        if packed && key_len <= MAX_DENSE_K {
+            KmerMap::Dense(DenseCounts::new(1 << (2 * key_len), width))
+        } else if concurrent && packed {
+            KmerMap::ConcurrentPacked(DashMap::with_hasher(FxBuildHasher))
+        } else if concurrent {
+            KmerMap::ConcurrentBytes(DashMap::with_hasher(FxBuildHasher))
+        } else if packed {
+            KmerMap::Packed((0..shards).map(|_| FxHashMap::default()).collect())
+        } else {
+            KmerMap::Bytes((0..shards).map(|_| FxHashMap::default()).collect())
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            KmerMap::Dense(dense) => dense.distinct,
+            KmerMap::Packed(shards) => shards.iter().map(|m| m.len()).sum(),
+            KmerMap::Bytes(shards) => shards.iter().map(|m| m.len()).sum(),
+            KmerMap::ConcurrentPacked(map) => map.len(),
+            KmerMap::ConcurrentBytes(map) => map.len(),
+            KmerMap::Estimate(hll) => hll.estimate().round() as usize,
+            KmerMap::CountMin(state) => state.distinct.estimate().round() as usize,
+        }
+    }
+
+    /// Approximate heap bytes held, given the length of byte keys: the hash table
+    /// buckets plus every byte key's own allocation, or the sketches
+    fn memory_bytes(&self, key_len: usize) -> usize {
+        match self {
+            KmerMap::Dense(dense) => dense.keys() * dense.width().bits() as usize / 8,
+            KmerMap::Packed(shards) => shards.iter().map(table_bytes).sum(),
+            KmerMap::Bytes(shards) => shards.iter().map(|m| table_bytes(m) + m.len() * key_len).sum(),
+            KmerMap::ConcurrentPacked(map) => shared_table_bytes(map),
+            KmerMap::ConcurrentBytes(map) => shared_table_bytes(map) + map.len() * key_len,
+            KmerMap::Estimate(hll) => hll.memory_bytes(),
+            KmerMap::CountMin(state) => {
+                state.sketch.memory_bytes() + state.distinct.memory_bytes() + state.heavy.memory_bytes()
+            }
+        }
+    }
+
+    /// Upper estimate of the peak bytes while up to `new_keys` more keys are inserted,
+    /// spread evenly over the shards: a table that would fill up is rehashed into a
+    /// larger one, and both are allocated until the move is done
+    fn peak_bytes(&self, key_len: usize, new_keys: usize) -> usize {
+        fn peak<K>(map: &FxHashMap<K, u16>, new_keys: usize) -> usize {
+            let needed = map.len() + new_keys;
+            if needed <= map.capacity() {
+                return table_bytes(map);
+            }
+            table_bytes(map) + buckets_for(needed.max(map.capacity() + 1)) * (std::mem::size_of::<(K, u16)>() + 1)
+        }
+        match self {
+            KmerMap::Packed(shards) => {
+                let per_shard = new_keys.div_ceil(shards.len());
+                shards.iter().map(|m| peak(m, per_shard)).sum()
+            }
+            KmerMap::Bytes(shards) => {
+                let per_shard = new_keys.div_ceil(shards.len());
+                shards.iter().map(|m| peak(m, per_shard)).sum::<usize>() + (self.len() + new_keys) * key_len
+            }
+            KmerMap::ConcurrentPacked(map) => shared_peak(map, new_keys),
+            KmerMap::ConcurrentBytes(map) => shared_peak(map, new_keys) + (self.len() + new_keys) * key_len,
+            KmerMap::Dense(_) | KmerMap::Estimate(_) | KmerMap::CountMin(_) => self.memory_bytes(key_len),
+        }
+    }
+}
+
+/// Buckets of a hash table holding up to `capacity` entries: a power of two kept at most 7/8 full
+fn buckets_for(capacity: usize) -> usize {
+    match capacity {
+        0 => 0,
+        1..4 => 4,
+        4..8 => 8,
+        _ => (capacity * 8 / 7).next_power_of_two(),
+    }
+}
+
+/// Bytes allocated by a hash table: its entries plus one control byte per bucket
+fn table_bytes<K>(map: &FxHashMap<K, u16>) -> usize {
+    buckets_for(map.capacity()) * (std::mem::size_of::<(K, u16)>() + 1)
+}
+
+/// Bytes allocated by a shared map, taking its capacity as one table's
+fn shared_table_bytes<K: Hash + Eq>(map: &SharedMap<K>) -> usize {
+    buckets_for(map.capacity()) * (std::mem::size_of::<(K, u16)>() + 1)
+}
+
+/// Upper estimate of a shared map's bytes while `new_keys` more keys are inserted,
+/// as if all its shards grew at once
+fn shared_peak<K: Hash + Eq>(map: &SharedMap<K>, new_keys: usize) -> usize {
+    let needed = map.len() + new_keys;
+    if needed <= map.capacity() {
+        return shared_table_bytes(map);
+    }
+    shared_table_bytes(map) + buckets_for(needed.max(map.capacity() + 1)) * (std::mem::size_of::<(K, u16)>() + 1)
+}
+
+/// Shard owning `key`. The hash is remixed and its high bits are used so that
+/// keys within one shard still spread over the shard map's own buckets.
+fn shard_of<K: Hash>(key: &K, shards: usize) -> usize {
+    if shards == 1 {
+        return 0;
+    }
+    let hash = FxBuildHasher.hash_one(key).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    (hash >> (64 - shards.trailing_zeros())) as usize
+}
+
+/// Well-mixed hash of a key, as fed to the sketches, under a `salt` derived from the
+/// hash seed (0 leaves the hash unsalted)
+fn sketch_hash<K: Hash>(key: &K, salt: u64) -> u64 {
+    mix64(FxBuildHasher.hash_one(key) ^ salt)
+}
+
+/// Record one observation of `key`, returning true if it just reached `min_count`.
+/// Counts stop at `saturate_at`, which is `min_count` unless full counts are tracked.
+fn observe<K: Hash + Eq>(map: &mut FxHashMap<K, u16>, key: K, min_count: u16, saturate_at: u16) -> bool {
+    bump(map.entry(key).or_insert(0), min_count, saturate_at)
+}
+
+/// Same as [`observe`] for a borrowed byte key, only copied when it is new to the map
+fn observe_slice(map: &mut FxHashMap<Vec<u8>, u16>, key: &[u8], min_count: u16, saturate_at: u16) -> bool {
+    if let Some(count) = map.get_mut(key) {
+        return bump(count, min_count, saturate_at);
+    }
+    let mut count = 0;
+    let solid = bump(&mut count, min_count, saturate_at);
+    map.insert(key.to_vec(), count);
+    solid
+}
+
+/// [`observe`] for a map other threads update at the same time
+fn observe_shared<K: Hash + Eq>(map: &SharedMap<K>, key: K, min_count: u16, saturate_at: u16) -> bool {
+    bump(&mut map.entry(key).or_insert(0), min_count, saturate_at)
+}
+
+/// [`observe_slice`] for a map other threads update at the same time
+fn observe_shared_slice(map: &SharedMap<Vec<u8>>, key: &[u8], min_count: u16, saturate_at: u16) -> bool {
+    if let Some(mut count) = map.get_mut(key) {
+        return bump(&mut count, min_count, saturate_at);
+    }
+    // Another thread may insert the key meanwhile, which the entry catches
+    bump(&mut map.entry(key.to_vec()).or_insert(0), min_count, saturate_at)
+}
+
+fn bump(count: &mut u16, min_count: u16, saturate_at: u16) -> bool {
+    if *count < saturate_at {
+        *count += 1;
+        return *count == min_count;
+    }
+    false
+}
+
+/// Record `occurrences` observations of `key` at once, returning true if it
+/// crossed `min_count`
+fn observe_count<K: Hash + Eq>(
+    map: &mut FxHashMap<K, u16>,
+    key: K,
+    occurrences: u16,
+    min_count: u16,
+    saturate_at: u16,
+) -> bool {
+    let count = map.entry(key).or_insert(0);
+    let before = *count;
+    *count = before.saturating_add(occurrences).min(saturate_at);
+    before < min_count && *count >= min_count
+}
+
+/// [`observe_count`] for a shared map
+fn observe_count_shared<K: Hash + Eq>(
+    map: &SharedMap<K>,
+    key: K,
+    occurrences: u16,
+    min_count: u16,
+    saturate_at: u16,
+) -> bool {
+    let mut count = map.entry(key).or_insert(0);
+    let before = *count;
+    *count = before.saturating_add(occurrences).min(saturate_at);
+    before < min_count && *count >= min_count
+}
+
+/// Insert pre-bucketed keys into their shards in parallel, returning how many became solid
+fn observe_buckets<K: Hash + Eq + Send>(
+    shards: &mut [FxHashMap<K, u16>],
+    buckets: Vec<Vec<Vec<K>>>,
+    min_count: u16,
+    saturate_at: u16,
+) -> u64 {
+    // Transpose so each shard gets the keys every chunk produced for it
+    let mut per_shard: Vec<Vec<Vec<K>>> = (0..shards.len()).map(|_| Vec::new()).collect();
+    for chunk in buckets {
+        for (shard, keys) in chunk.into_iter().enumerate() {
+            per_shard[shard].push(keys);
+        }
+    }
+    shards
+        .par_iter_mut()
+        .zip(per_shard)
+        .map(|(map, chunks)| {
+            let mut solid = 0;
+            for key in chunks.into_iter().flatten() {
+                if observe(map, key, min_count, saturate_at) {
+                    solid += 1;
+                }
+            }
+            solid
+        })
+        .sum()
+}
+
+/// Keys solid in both `a` and `b`, whose shards may differ in number
+fn shared_solid<K: Hash + Eq + Sync>(a: &[FxHashMap<K, u16>], b: &[FxHashMap<K, u16>], a_min: u16, b_min: u16) -> u64 {
+    a.par_iter()
+        .map(|shard| {
+            shard
+                .iter()
+                .filter(|&(key, &count)| {
+                    count >= a_min && b[shard_of(key, b.len())].get(key).is_some_and(|&count| count >= b_min)
+                })
+                .count() as u64
+        })
+        .sum()
+}
+
+/// Keys solid in both shared maps
+fn shared_solid_concurrent<K: Hash + Eq + Send + Sync>(a: &SharedMap<K>, b: &SharedMap<K>, a_min: u16, b_min: u16) -> u64 {
+    a.par_iter()
+        .filter(|entry| *entry.value() >= a_min && b.get(entry.key()).is_some_and(|count| *count >= b_min))
+        .count() as u64
+}
+
+impl DenseCounts {
+    /// A table of `keys` zero counts
+    fn new(keys: usize, width: CountWidth) -> Self {
+        let cells = match width {
+            CountWidth::U8 => DenseCells::Narrow(vec![0; keys]),
+            CountWidth::U16 => DenseCells::Wide(vec![0; keys]),
+        };
+        DenseCounts { cells, distinct: 0 }
+    }
+
+    fn width(&self) -> CountWidth {
+        match &self.cells {
+            DenseCells::Narrow(_) => CountWidth::U8,
+            DenseCells::Wide(_) => CountWidth::U16,
+        }
+    }
+
+    /// Number of cells, one per possible key
+    fn keys(&self) -> usize {
+        match &self.cells {
+            DenseCells::Narrow(cells) => cells.len(),
+            DenseCells::Wide(cells) => cells.len(),
+        }
+    }
+
+    fn count(&self, key: u64) -> u16 {
+        match &self.cells {
+            DenseCells::Narrow(cells) => cells[key as usize].into(),
+            DenseCells::Wide(cells) => cells[key as usize],
+        }
+    }
+
+    /// Record one observation of `key`, as [`observe`] does; counts also stop at the table's width
+    fn observe(&mut self, key: u64, min_count: u16, saturate_at: u16) -> bool {
+        let (solid, new) = match &mut self.cells {
+            DenseCells::Narrow(cells) => bump_cell(&mut cells[key as usize], min_count, saturate_at),
+            DenseCells::Wide(cells) => bump_cell(&mut cells[key as usize], min_count, saturate_at),
+        };
+        self.distinct += new as usize;
+        solid
+    }
+
+    /// Record `occurrences` observations of `key` at once, as [`observe_count`] does
+    fn observe_count(&mut self, key: u64, occurrences: u16, min_count: u16, saturate_at: u16) -> bool {
+        let (before, after) = match &mut self.cells {
+            DenseCells::Narrow(cells) => add_to_cell(&mut cells[key as usize], occurrences, saturate_at),
+            DenseCells::Wide(cells) => add_to_cell(&mut cells[key as usize], occurrences, saturate_at),
+        };
+        self.distinct += (before == 0 && after > 0) as usize;
+        before < min_count && after >= min_count
+    }
+
+    /// Log2 of the keys per range when the array is split into one contiguous range
+    /// per shard, updated in parallel. Canonical keys crowd the low end of the array;
+    /// shards outnumber threads, so work stealing evens that out.
+    fn range_bits(&self, shards: usize) -> u32 {
+        (self.keys() / shards).max(1).trailing_zeros()
+    }
+
+    /// Number of ranges of `2^bits` keys
+    fn ranges(&self, bits: u32) -> usize {
+        self.keys() >> bits
+    }
+
+    /// Insert keys bucketed by `key >> bits` in parallel, returning how many became solid
+    fn observe_buckets(&mut self, buckets: Vec<Vec<Vec<u64>>>, bits: u32, min_count: u16, saturate_at: u16) -> u64 {
+        let mut per_shard: Vec<Vec<Vec<u64>>> = (0..self.ranges(bits)).map(|_| Vec::new()).collect();
+        for chunk in buckets {
+            for (shard, keys) in chunk.into_iter().enumerate().filter(|(_, keys)| !keys.is_empty()) {
+                per_shard[shard].push(keys);
+            }
+        }
+        let (distinct, solid) = match &mut self.cells {
+            DenseCells::Narrow(cells) => observe_ranges(cells, per_shard, bits, min_count, saturate_at),
+            DenseCells::Wide(cells) => observe_ranges(cells, per_shard, bits, min_count, saturate_at),
+        };
+        self.distinct += distinct;
+        solid
+    }
+
+    /// Keys solid in both arrays
+    fn shared_solid(&self, other: &DenseCounts, min_count: u16, other_min_count: u16) -> u64 {
+        use DenseCells::{Narrow, Wide};
+        let thresholds = (min_count, other_min_count);
+        match (&self.cells, &other.cells) {
+            (Narrow(a), Narrow(b)) => shared_solid_cells(a, b, thresholds),
+            (Narrow(a), Wide(b)) => shared_solid_cells(a, b, thresholds),
+            (Wide(a), Narrow(b)) => shared_solid_cells(a, b, thresholds),
+            (Wide(a), Wide(b)) => shared_solid_cells(a, b, thresholds),
+        }
+    }
+
+    /// Stored keys with their counts, in key order
+    fn iter(&self) -> impl Iterator<Item = (u64, u16)> + '_ {
+        (0..self.keys() as u64).map(|key| (key, self.count(key))).filter(|&(_, count)| count > 0)
+    }
+}
+
+/// Record one observation in a dense cell, as [`bump`] does, stopping at the cell's width
+/// too. Returns whether it just reached `min_count` and whether it was empty.
+fn bump_cell<C: Cell>(cell: &mut C, min_count: u16, saturate_at: u16) -> (bool, bool) {
+    let count: u16 = (*cell).into();
+    if count < saturate_at.min(C::MAX) {
+        *cell = C::from_count(count + 1);
+        return (count + 1 == min_count, count == 0);
+    }
+    (false, false)
+}
+
+/// Add `occurrences` to a dense cell, returning its count before and after
+fn add_to_cell<C: Cell>(cell: &mut C, occurrences: u16, saturate_at: u16) -> (u16, u16) {
+    let before: u16 = (*cell).into();
+    let after = before.saturating_add(occurrences).min(saturate_at).min(C::MAX);
+    *cell = C::from_count(after);
+    (before, after)
+}
+
+/// [`DenseCounts::observe_buckets`] over the cells of one width, one range of `2^bits`
+/// cells per shard, returning the keys that became distinct and solid
+fn observe_ranges<C: Cell>(
+    cells: &mut [C],
+    per_shard: Vec<Vec<Vec<u64>>>,
+    bits: u32,
+    min_count: u16,
+    saturate_at: u16,
+) -> (usize, u64) {
+    cells
+        .par_chunks_mut(1 << bits)
+        .zip(per_shard)
+        .map(|(cells, chunks)| {
+            let (mut distinct, mut solid) = (0, 0);
+            for key in chunks.into_iter().flatten() {
+                let (became_solid, new) = bump_cell(&mut cells[key as usize & ((1 << bits) - 1)], min_count, saturate_at);
+                distinct += new as usize;
+                solid += became_solid as u64;
+            }
+            (distinct, solid)
+        })
+        .reduce(|| (0, 0), |(da, sa), (db, sb)| (da + db, sa + sb))
+}
+
+/// Keys whose cells are at least the `(a, b)` thresholds in both tables
+fn shared_solid_cells<A: Cell, B: Cell>(a: &[A], b: &[B], (min_a, min_b): (u16, u16)) -> u64 {
+    a.par_iter().zip(b).filter(|&(&a, &b)| a.into() >= min_a && b.into() >= min_b).count() as u64
+}
+
+/// Windows of a scan: those counted, those left out of the count by reason, and
+/// the strands of the counted ones when they are tallied
+#[derive(Clone, Copy, Debug, Default)]
+struct ScanTally {
+    /// Passing every filter, whether or not they were then picked as minimizers
+    counted: u64,
+    /// Containing a base other than A/C/G/T (or A/C/G/U)
+    ambiguous: u64,
+    /// Holding a homopolymer longer than the limit
+    homopolymer: u64,
+    /// Dinucleotide entropy below the masking threshold
+    low_complexity: u64,
+    /// GC fraction outside the allowed range
+    gc: u64,
+    strands: StrandCounts,
+}
+
+impl std::ops::Add for ScanTally {
+    type Output = ScanTally;
+
+    fn add(self, other: ScanTally) -> ScanTally {
+        ScanTally {
+            counted: self.counted + other.counted,
+            ambiguous: self.ambiguous + other.ambiguous,
+            homopolymer: self.homopolymer + other.homopolymer,
+            low_complexity: self.low_complexity + other.low_complexity,
+            gc: self.gc + other.gc,
+            strands: self.strands + other.strands,
+        }
+    }
+}
+
+impl std::ops::AddAssign for ScanTally {
+    fn add_assign(&mut self, other: ScanTally) {
+        *self = *self + other;
+    }
+}
+
+impl std::iter::Sum for ScanTally {
+    fn sum<I: Iterator<Item = ScanTally>>(iter: I) -> ScanTally {
+        iter.fold(ScanTally::default(), |a, b| a + b)
+    }
+}
+
+/// Observations of canonical k-mers by the strand they were read from, see
+/// [`KmerCounter::set_strand_stats`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StrandCounts {
+    /// The k-mer as read was its canonical form
+    pub forward: u64,
+    /// The k-mer as read was the reverse complement of its canonical form
+    pub reverse: u64,
+    /// The k-mer is its own reverse complement, so reads the same on both strands
+    pub palindromic: u64,
+}
+
+impl StrandCounts {
+    /// Tally a k-mer by how it compares to its reverse complement
+    fn observe(&mut self, order: Ordering) {
+        match order {
+            Ordering::Less => self.forward += 1,
+            Ordering::Greater => self.reverse += 1,
+            Ordering::Equal => self.palindromic += 1,
+        }
+    }
+
+    /// Share of the observations on either strand that were forward, 0.5 without any
+    pub fn forward_fraction(&self) -> f64 {
+        match self.forward + self.reverse {
+            0 => 0.5,
+            stranded => self.forward as f64 / stranded as f64,
+        }
+    }
+}
+
+impl std::ops::Add for StrandCounts {
+    type Output = StrandCounts;
+
+    fn add(self, other: StrandCounts) -> StrandCounts {
+        StrandCounts {
+            forward: self.forward + other.forward,
+            reverse: self.reverse + other.reverse,
+            palindromic: self.palindromic + other.palindromic,
+        }
+    }
+}
+
+/// Settings deciding which windows of a sequence are counted and how they are keyed
+struct Scanner {
+    k: usize,
+    min_count: u16,
+    saturate_at: u16,
+    allow_ambiguous: bool,
+    rna: bool,
+    /// Windows are peptides: amino acids are the valid residues and there is no reverse complement
+    protein: bool,
+    /// Key windows by the smaller of the k-mer and its reverse complement
+    canonical: bool,
+    /// Tally the strand each counted window was canonicalized from
+    strand_stats: bool,
+    /// Offsets read from each window of a spaced seed; other positions are ignored
+    seed: Option<Vec<usize>>,
+    /// Only count the minimizer of every this many consecutive k-mers
+    minimizer_window: Option<usize>,
+    packed: bool,
+    /// Sketch windows by their rolling ntHash instead of building their keys
+    nthash: bool,
+    /// Seed of the MinHash hashes, see [`KmerCounter::set_hash_seed`]
+    hash_seed: u32,
+    /// The hash seed mixed into a salt for the estimate and Count-Min sketch hashes
+    hash_salt: u64,
+    /// Skip windows whose dinucleotide entropy (bits) is below this
+    min_entropy: Option<f64>,
+    /// Skip windows holding a run of one base longer than this
+    max_homopolymer: Option<usize>,
+    /// Skip windows whose GC fraction is outside this inclusive range
+    gc_range: Option<(f64, f64)>,
+}
+
+impl Scanner {
+    /// Length of the keys: k, or the number of bases a spaced seed reads
+    fn key_len(&self) -> usize {
+        self.seed.as_ref().map_or(self.k, Vec::len)
+    }
+
+    /// Whether counted windows are tallied by strand: only canonical k-mers have two, and
+    /// the windows minimizers are picked from are not all counted
+    fn tallies_strands(&self) -> bool {
+        self.strand_stats && self.canonical && self.minimizer_window.is_none()
+    }
+
+    /// Whether `base` may be part of a counted key: an unambiguous nucleotide or, in
+    /// protein mode, a standard amino acid
+    fn is_valid(&self, base: u8) -> bool {
+        if self.protein { is_amino_acid(base) } else { is_unambiguous(base, self.rna) }
+    }
+
+    /// Call `f(start, packed)` for every window of `seq` to count, where
+    /// `packed` is the 2-bit encoding of its key (only meaningful for the
+    /// packed backend). Returns the windows skipped, by reason.
+    fn scan(&self, seq: &[u8], f: impl FnMut(usize, u64)) -> ScanTally {
+        match self.minimizer_window {
+            Some(w) => self.scan_minimizers(w, seq, f),
+            None => self.scan_windows(seq, f),
+        }
+    }
+
+    /// Call `f` for the minimizer (smallest key hash, leftmost on ties) of
+    /// every `w` consecutive countable windows, once per position even when
+    /// it stays the minimizer of several windows. Skipped windows (ambiguous or
+    /// masked) end a run; a run shorter than `w` still yields its minimizer.
+    fn scan_minimizers(&self, w: usize, seq: &[u8], mut f: impl FnMut(usize, u64)) -> ScanTally {
+        // Candidates by increasing start, with increasing hashes (monotonic deque)
+        let mut candidates: VecDeque<(usize, u64, u64)> = VecDeque::with_capacity(w);
+        let mut run_start = 0;
+        let mut last_start = None;
+        let mut last_emitted = None;
+        let mut key = Vec::new();
+        let mut emit = |candidates: &VecDeque<(usize, u64, u64)>, f: &mut dyn FnMut(usize, u64)| {
+            match candidates.front() {
+                Some(&(start, packed, _)) if last_emitted != Some(start) => {
+                    last_emitted = Some(start);
+                    f(start, packed);
+                }
+                _ => {}
+            }
+        };
+
+        let skipped = self.scan_windows(seq, |start, packed| {
+            if last_start.is_none_or(|last| start != last + 1) {
+                // A skipped window broke the run; flush a run too short to have emitted
+                if last_start.is_some_and(|last| last + 1 - run_start < w) {
+                    emit(&candidates, &mut f);
+                }
+                candidates.clear();
+                run_start = start;
+            }
+            last_start = Some(start);
+
+            // Which k-mers are minimizers decides what gets counted, so it is left unseeded
+            let hash = if self.packed {
+                sketch_hash(&packed, 0)
+            } else {
+                self.bytes_key_into(seq, start, &mut key);
+                sketch_hash(&key, 0)
+            };
+            while candidates.back().is_some_and(|&(_, _, back)| back > hash) {
+                candidates.pop_back();
+            }
+            candidates.push_back((start, packed, hash));
+            while candidates.front().is_some_and(|&(front, _, _)| front + w <= start) {
+                candidates.pop_front();
+            }
+            if start + 1 - run_start >= w {
+                emit(&candidates, &mut f);
+            }
+        });
+        if last_start.is_some_and(|last| last + 1 - run_start < w) {
+            emit(&candidates, &mut f);
+        }
+        skipped
+    }
+
+    /// Call `f(start, packed)` for every countable window of `seq`. Returns the
+    /// windows skipped.
+    fn scan_windows(&self, seq: &[u8], mut f: impl FnMut(usize, u64)) -> ScanTally {
+        let k = self.k;
+        if seq.len() < k {
+            return ScanTally::default();
+        }
+        if let Some(seed) = &self.seed {
+            return self.scan_spaced(seed, seq, f);
+        }
+
+        let mask = match k {
+            MAX_PACKED_K => u64::MAX,
+            _ if self.packed => (1u64 << (2 * k)) - 1,
+            _ => 0,
+        };
+        let rc_shift = 2 * k.saturating_sub(1);
+        let mut forward = 0u64;
+        let mut reverse = 0u64;
+        let mut skipped = ScanTally::default();
+        let mut entropy = self.min_entropy.map(|_| DinucleotideEntropy::new(k));
+        let mut homopolymers = self.max_homopolymer.map(HomopolymerRuns::new);
+        let mut gc = self.gc_range.map(|_| GcWindow::new(k));
+        let strand_stats = self.tallies_strands();
+
+        // Length of the run of valid bases ending at the current position;
+        // a window is only counted once the run covers all k bases, which
+        // fast-forwards past the last ambiguous base without re-scanning.
+        // Homopolymers are followed the same way, by where the last long run ended,
+        // and the GC content by the bases entering and leaving the window.
+        let mut valid_run = 0;
+        for (end, &base) in seq.iter().enumerate() {
+            if self.allow_ambiguous || self.is_valid(base) {
+                valid_run += 1;
+            } else {
+                valid_run = 0;
+            }
+            if self.packed {
+                let code = base_code(base);
+                forward = ((forward << 2) | code) & mask;
+                reverse = (reverse >> 2) | ((3 - code) << rc_shift);
+            }
+            if let Some(entropy) = entropy.as_mut() {
+                entropy.advance(seq, end);
+            }
+            if let Some(homopolymers) = homopolymers.as_mut() {
+                homopolymers.advance(seq, end);
+            }
+            if let Some(gc) = gc.as_mut() {
+                gc.advance(seq, end);
+            }
+            if end + 1 < k {
+                continue;
+            }
+            if valid_run < k {
+                skipped.ambiguous += 1;
+                continue;
+            }
+            if homopolymers.as_ref().is_some_and(|runs| runs.exceeded_since(end + 1 - k)) {
+                skipped.homopolymer += 1;
+                continue;
+            }
+            if self.is_low_complexity(entropy.as_ref()) {
+                skipped.low_complexity += 1;
+                continue;
+            }
+            if self.is_outside_gc(gc.as_ref().map(GcWindow::fraction)) {
+                skipped.gc += 1;
+                continue;
+            }
+            if strand_stats {
+                let order = match self.packed {
+                    true => forward.cmp(&reverse),
+                    false => strand_order(&seq[end + 1 - k..=end], self.rna),
+                };
+                skipped.strands.observe(order);
+            }
+            skipped.counted += 1;
+            f(end + 1 - k, if self.canonical { forward.min(reverse) } else { forward });
+        }
+        skipped
+    }
+
+    /// Call `f(start, hash)` for every countable window of `seq` with its
+    /// ntHash, canonical unless forward-only. Returns the windows skipped.
+    fn scan_nthash(&self, seq: &[u8], mut f: impl FnMut(usize, u64)) -> ScanTally {
+        let k = self.k;
+        let mut hash: Option<NtHash> = None;
+        let mut skipped = ScanTally::default();
+        let mut entropy = self.min_entropy.map(|_| DinucleotideEntropy::new(k));
+        let mut homopolymers = self.max_homopolymer.map(HomopolymerRuns::new);
+        let mut gc = self.gc_range.map(|_| GcWindow::new(k));
+        let mut valid_run = 0;
+        for (end, &base) in seq.iter().enumerate() {
+            if is_unambiguous(base, self.rna) {
+                valid_run += 1;
+            } else {
+                valid_run = 0;
+                hash = None;
+            }
+            if let Some(entropy) = entropy.as_mut() {
+                entropy.advance(seq, end);
+            }
+            if let Some(homopolymers) = homopolymers.as_mut() {
+                homopolymers.advance(seq, end);
+            }
+            if let Some(gc) = gc.as_mut() {
+                gc.advance(seq, end);
+            }
+            if end + 1 < k {
+                continue;
+            }
+            if valid_run < k {
+                skipped.ambiguous += 1;
+                continue;
+            }
+            let start = end + 1 - k;
+            let homopolymer = homopolymers.as_ref().is_some_and(|runs| runs.exceeded_since(start));
+            let low_complexity = !homopolymer && self.is_low_complexity(entropy.as_ref());
+            if homopolymer || low_complexity || self.is_outside_gc(gc.as_ref().map(GcWindow::fraction)) {
+                // The rolling hash still has to follow the window
+                if let Some(window) = hash.as_mut() {
+                    window.roll(seq[start - 1], base);
+                }
+                if homopolymer {
+                    skipped.homopolymer += 1;
+                } else if low_complexity {
+                    skipped.low_complexity += 1;
+                } else {
+                    skipped.gc += 1;
+                }
+                continue;
+            }
+            let window = match &mut hash {
+                Some(window) => {
+                    window.roll(seq[start - 1], base);
+                    window
+                }
+                None => hash.insert(NtHash::new(&seq[start..=end])),
+            };
+            skipped.counted += 1;
+            f(start, mix64((if self.canonical { window.canonical() } else { window.forward() }) ^ self.hash_salt));
+        }
+        skipped
+    }
+
+    /// Whether the full window tracked by `entropy` is masked as low-complexity
+    fn is_low_complexity(&self, entropy: Option<&DinucleotideEntropy>) -> bool {
+        match (self.min_entropy, entropy) {
+            (Some(min), Some(entropy)) => entropy.entropy() < min,
+            _ => false,
+        }
+    }
+
+    /// Whether a window of GC fraction `gc` falls outside the allowed range
+    fn is_outside_gc(&self, gc: Option<f64>) -> bool {
+        match (self.gc_range, gc) {
+            (Some((min, max)), Some(gc)) => gc < min || gc > max,
+            _ => false,
+        }
+    }
+
+    /// `scan` for a spaced seed: only the bases at `seed` offsets have to be
+    /// valid and make up the key. Windows are not contiguous, so each one is
+    /// checked and packed on its own.
+    fn scan_spaced(&self, seed: &[usize], seq: &[u8], mut f: impl FnMut(usize, u64)) -> ScanTally {
+        let mut skipped = ScanTally::default();
+        for (start, window) in seq.windows(self.k).enumerate() {
+            if !self.allow_ambiguous && !seed.iter().all(|&i| self.is_valid(window[i])) {
+                skipped.ambiguous += 1;
+                continue;
+            }
+            // The whole window is checked, as a repeat is just as uninformative under a seed
+            if self.max_homopolymer.is_some_and(|max| HomopolymerRuns::in_window(window, max)) {
+                skipped.homopolymer += 1;
+                continue;
+            }
+            if self.min_entropy.is_some_and(|min| DinucleotideEntropy::of_window(window) < min) {
+                skipped.low_complexity += 1;
+                continue;
+            }
+            if self.gc_range.is_some_and(|(min, max)| !(min..=max).contains(&GcWindow::of_window(window))) {
+                skipped.gc += 1;
+                continue;
+            }
+            let mut packed = 0;
+            if self.packed {
+                let forward = seed.iter().fold(0, |acc, &i| (acc << 2) | base_code(window[i]));
+                let reverse = seed.iter().rev().fold(0, |acc, &i| (acc << 2) | (3 - base_code(window[i])));
+                packed = if self.canonical { forward.min(reverse) } else { forward };
+                if self.tallies_strands() {
+                    skipped.strands.observe(forward.cmp(&reverse));
+                }
+            } else if self.tallies_strands() {
+                let key: Vec<u8> = seed.iter().map(|&i| window[i]).collect();
+                skipped.strands.observe(strand_order(&key, self.rna));
+            }
+            skipped.counted += 1;
+            f(start, packed);
+        }
+        skipped
+    }
+
+    /// Byte key of the window starting at `start`
+    fn bytes_key(&self, seq: &[u8], start: usize) -> Vec<u8> {
+        let mut key = Vec::with_capacity(self.key_len());
+        self.bytes_key_into(seq, start, &mut key);
+        key
+    }
+
+    /// Write the byte key of the window starting at `start` into `key`,
+    /// reusing its allocation
+    fn bytes_key_into(&self, seq: &[u8], start: usize, key: &mut Vec<u8>) {
+        let window = &seq[start..start + self.k];
+        key.clear();
+        match &self.seed {
+            Some(seed) => key.extend(seed.iter().map(|&i| window[i])),
+            None => key.extend_from_slice(window),
+        }
+        if self.canonical {
+            canonicalize(key, self.rna);
+        }
+    }
+
+    /// Feed every countable window of `seq` into `hll`, returning the windows skipped
+    fn scan_into_sketch(&self, seq: &[u8], hll: &mut HyperLogLog) -> ScanTally {
+        if self.nthash {
+            self.scan_nthash(seq, |_, hash| hll.insert_hash(hash))
+        } else if self.packed {
+            self.scan(seq, |_, key| hll.insert_hash(sketch_hash(&key, self.hash_salt)))
+        } else {
+            let mut key = Vec::with_capacity(self.key_len());
+            self.scan(seq, |start, _| {
+                self.bytes_key_into(seq, start, &mut key);
+                hll.insert_hash(sketch_hash(&key, self.hash_salt));
+            })
+        }
+    }
+
+    /// Add the MinHash of every countable window of `seq` to `minhash`: the
+    /// stable hash of its key bytes, built in `key`
+    fn scan_into_minhash(&self, seq: &[u8], minhash: &mut MinHash, key: &mut Vec<u8>) {
+        self.scan(seq, |start, packed| {
+            if self.packed {
+                unpack_kmer_into(packed, self.key_len(), self.rna, key);
+            } else {
+                self.bytes_key_into(seq, start, key);
+            }
+            minhash.insert_hash(murmur3_64(key, self.hash_seed));
+        });
+    }
+
+    /// Count every countable window of `seq` in the Count-Min sketch,
+    /// returning how many became solid and how many windows were skipped
+    fn scan_into_count_min(&self, seq: &[u8], state: &mut CountMinState) -> (u64, ScanTally) {
+        let mut solid = 0;
+        let mut observe = |hash: u64, kmer: &dyn Fn() -> Vec<u8>| {
+            state.distinct.insert_hash(hash);
+            let count = state.sketch.insert_hash(hash).saturating_add(1);
+            if count == self.min_count as u32 {
+                solid += 1;
+            }
+            if count > state.heavy.min_count() {
+                state.heavy.offer(hash, count, kmer);
+            }
+        };
+        let skipped = if self.nthash {
+            self.scan_nthash(seq, |start, hash| observe(hash, &|| self.bytes_key(seq, start)))
+        } else if self.packed {
+            self.scan(seq, |_, key| observe(sketch_hash(&key, self.hash_salt), &|| unpack_kmer(key, self.key_len(), self.rna)))
+        } else {
+            let mut key = Vec::with_capacity(self.key_len());
+            self.scan(seq, |start, _| {
+                self.bytes_key_into(seq, start, &mut key);
+                observe(sketch_hash(&key, self.hash_salt), &|| key.clone())
+            })
+        };
+        (solid, skipped)
+    }
+}
+
+/// Extract keys from a batch in parallel chunks, bucketed by destination shard.
+/// Returns the buckets of every chunk and the number of windows skipped.
+fn bucketed<K: Send, S: AsRef<[u8]> + Sync>(
+    seqs: &[S],
+    shards: usize,
+    extract: impl Fn(&[u8], &mut Vec<Vec<K>>) -> ScanTally + Sync,
+) -> (Vec<Vec<Vec<K>>>, ScanTally) {
+    let chunk_size = seqs.len().div_ceil(rayon::current_num_threads()).max(1);
+    let results: Vec<(Vec<Vec<K>>, ScanTally)> = seqs
+        .par_chunks(chunk_size)
+        .map(|chunk| {
+            let mut buckets: Vec<Vec<K>> = (0..shards).map(|_| Vec::new()).collect();
+            let skipped = chunk.iter().map(|seq| extract(seq.as_ref(), &mut buckets)).sum();
+            (buckets, skipped)
+        })
+        .collect();
+    let skipped = results.iter().map(|&(_, s)| s).sum();
+    (results.into_iter().map(|(b, _)| b).collect(), skipped)
+}
+
+/// Streaming counter of distinct and solid (seen at least `min_count` times) canonical k-mers
+/// (or k-mers as read, see `set_forward_only`)
+pub struct KmerCounter {
+    scanner: Scanner,
+    case_sensitive: bool,
+    shards: usize,
+    estimate_precision: Option<u8>,
+    max_memory: Option<usize>,
+    nthash: bool,
+    /// Worker threads insert into one shared map instead of bucketing keys by shard
+    concurrent: bool,
+    /// Width of the dense table's counts, if not chosen from the counts kept
+    count_width: Option<CountWidth>,
+    kmers: KmerMap,
+    /// Bottom-k sketch of the counted k-mers, kept alongside `kmers` if requested
+    minhash: Option<MinHash>,
+    solid_kmers: u64,
+    tally: ScanTally,
+    /// Sequences added and, of those, sequences whose k-mers were all solid already, if tracked
+    redundancy: Option<(u64, u64)>,
+    /// Reused for the byte key of each window, so lookups of known k-mers don't allocate
+    scratch: Vec<u8>,
+}
+
+impl KmerCounter {
+    pub fn new(k: usize) -> Self {
+        assert!(k >= 1, "k must be at least 1");
+        let mut counter = KmerCounter {
+            scanner: Scanner {
+                k,
+                min_count: DEFAULT_MIN_COUNT,
+                saturate_at: DEFAULT_MIN_COUNT,
+                allow_ambiguous: false,
+                rna: false,
+                protein: false,
+                canonical: true,
+                strand_stats: false,
+                seed: None,
+                minimizer_window: None,
+                packed: false,
+                nthash: false,
+                hash_seed: MINHASH_SEED,
+                hash_salt: mix64(MINHASH_SEED as u64),
+                min_entropy: None,
+                max_homopolymer: None,
+                gc_range: None,
+            },
+            case_sensitive: false,
+            shards: 1,
+            estimate_precision: None,
+            max_memory: None,
+            nthash: false,
+            concurrent: false,
+            count_width: None,
+            kmers: KmerMap::new(false, k, 1, false, CountWidth::U16),
+            minhash: None,
+            solid_kmers: 0,
+            tally: ScanTally::default(),
+            redundancy: None,
+            scratch: Vec::new(),
+        };
+        counter.select_backend();
+        counter
+    }
+
+    /// Pick the k-mer storage for the current settings. Settings are only
+    /// changed before counting starts, so the (empty) map is simply replaced.
+    fn select_backend(&mut self) {
+        debug_assert_eq!(self.kmers.len(), 0, "counter settings changed after counting started");
+        let scanner = &mut self.scanner;
+        scanner.packed = (1..=MAX_PACKED_K).contains(&scanner.key_len())
+            && !scanner.allow_ambiguous
+            && !scanner.protein
+            && !self.case_sensitive;
+        let width = self.count_width.unwrap_or(CountWidth::for_counts(scanner.saturate_at));
+        assert!(
+            scanner.min_count <= width.max_count(),
+            "{}-bit counts cannot reach a min_count of {}",
+            width.bits(),
+            scanner.min_count
+        );
+        // A table of every possible key is kept when it would come back the same: allocating
+        // it again can hand back freed memory, which has to be zeroed page by page
+        let unchanged = matches!(
+            (self.max_memory, self.estimate_precision, &self.kmers),
+            (None, None, KmerMap::Dense(dense))
+                if scanner.packed
+                    && scanner.key_len() <= MAX_DENSE_K
+                    && dense.keys() == 1 << (2 * scanner.key_len())
+                    && dense.width() == width
+        );
+        if !unchanged {
+            self.kmers = match (self.max_memory, self.estimate_precision) {
+                (Some(bytes), _) => {
+                    let distinct = HyperLogLog::new(COUNT_MIN_HLL_PRECISION);
+                    let sketch_bytes = bytes.saturating_sub(1 << COUNT_MIN_HLL_PRECISION);
+                    KmerMap::CountMin(Box::new(CountMinState {
+                        sketch: CountMinSketch::with_memory(sketch_bytes, COUNT_MIN_DEPTH),
+                        distinct,
+                        heavy: HeavyHitters::new(HEAVY_HITTERS),
+                    }))
+                }
+                (None, Some(precision)) => KmerMap::Estimate(HyperLogLog::new(precision)),
+                (None, None) => KmerMap::new(scanner.packed, scanner.key_len(), self.shards, self.concurrent, width),
+            };
+        }
+        // ntHash only models contiguous windows of unambiguous, case-folded bases
+        scanner.nthash = self.nthash
+            && matches!(self.kmers, KmerMap::Estimate(_) | KmerMap::CountMin(_))
+            && scanner.seed.is_none()
+            && scanner.minimizer_window.is_none()
+            && !scanner.allow_ambiguous
+            && !scanner.protein
+            && !self.case_sensitive;
+    }
+
+    /// Keep k-mers containing bases other than A/C/G/T (or A/C/G/U for RNA).
+    /// Must be set before any sequence is added.
+    pub fn set_allow_ambiguous(&mut self, allow_ambiguous: bool) {
+        self.scanner.allow_ambiguous = allow_ambiguous;
+        self.select_backend();
+    }
+
+    /// Keep lowercase and uppercase bases distinct. Must be set before any sequence is added.
+    pub fn set_case_sensitive(&mut self, case_sensitive: bool) {
+        self.case_sensitive = case_sensitive;
+        self.select_backend();
+    }
+
+    /// Pair U with A when complementing. Must be set before any sequence is added.
+    pub fn set_rna(&mut self, rna: bool) {
+        self.scanner.rna = rna;
+        self.select_backend();
+    }
+
+    /// Count each k-mer as read instead of merging it with its reverse
+    /// complement, for strand-specific data. A k-mer and its reverse complement
+    /// then count as two distinct k-mers. Must be set before any sequence is added.
+    pub fn set_forward_only(&mut self, forward_only: bool) {
+        self.scanner.canonical = !forward_only && !self.scanner.protein;
+        self.select_backend();
+    }
+
+    /// Count k-peptides of protein sequences: only the 20 standard amino acids are
+    /// valid residues, and windows are keyed as read since proteins have no reverse
+    /// complement (forward-only is implied). Keys are stored as bytes.
+    /// Must be set before any sequence is added.
+    pub fn set_protein(&mut self, protein: bool) {
+        self.scanner.protein = protein;
+        if protein {
+            self.scanner.canonical = false;
+        }
+        self.select_backend();
+    }
+
+    /// Count spaced seeds instead of contiguous k-mers: `pattern` has one entry
+    /// per position of the k-long window, and only the `true` positions are
+    /// read into the key. Keys are canonicalized over the extracted bases.
+    /// Must be set before any sequence is added.
+    pub fn set_seed_pattern(&mut self, pattern: Option<&[bool]>) {
+        self.scanner.seed = pattern.map(|pattern| {
+            assert_eq!(pattern.len(), self.scanner.k, "seed pattern must be k positions long");
+            assert!(pattern.contains(&true), "seed pattern must read at least one position");
+            pattern.iter().enumerate().filter(|&(_, &read)| read).map(|(i, _)| i).collect()
+        });
+        self.select_backend();
+    }
+
+    /// Count only minimizers: of every `w` consecutive k-mers, the one with the
+    /// smallest hash. Far fewer keys are stored; `w = 1` counts every k-mer.
+    /// Must be set before any sequence is added.
+    pub fn set_minimizer_window(&mut self, w: Option<usize>) {
+        assert!(w != Some(0), "minimizer window must be at least 1");
+        self.scanner.minimizer_window = w;
+        self.select_backend();
+    }
+
+    /// Number of observations (at least 1) after which a k-mer counts as solid.
+    /// Must be set before any sequence is added.
+    pub fn set_min_count(&mut self, min_count: u16) {
+        assert!(min_count >= 1, "min_count must be at least 1");
+        self.scanner.min_count = min_count;
+        self.scanner.saturate_at = self.scanner.saturate_at.max(min_count);
+        self.select_backend();
+    }
+
+    /// Keep full per-k-mer counts (saturating at `u16::MAX`) rather than
+    /// stopping at `min_count`, as the abundance histogram needs.
+    /// Must be set before any sequence is added.
+    pub fn set_track_counts(&mut self, track_counts: bool) {
+        self.scanner.saturate_at = if track_counts { u16::MAX } else { self.scanner.min_count };
+        self.select_backend();
+    }
+
+    pub fn min_count(&self) -> u16 {
+        self.scanner.min_count
+    }
+
+    /// Tell, for every sequence added, whether each of its k-mers was already
+    /// solid before it: such a sequence adds nothing new. Exact counting only, and
+    /// batches are then counted one sequence after the other.
+    pub fn set_track_redundancy(&mut self, track: bool) {
+        self.redundancy = track.then_some((0, 0));
+    }
+
+    /// Tally, for every counted k-mer, whether the read held it as its canonical form or as
+    /// the reverse complement of it (see [`strand_counts`](Self::strand_counts)): a strong skew
+    /// can point at strand bias in library prep. Nothing is tallied for forward-only k-mers
+    /// (see [`set_forward_only`](Self::set_forward_only)), ntHash sketches or minimizers.
+    pub fn set_strand_stats(&mut self, strand_stats: bool) {
+        self.scanner.strand_stats = strand_stats;
+    }
+
+    /// Counted k-mers by the strand they were read from, when tallied with
+    /// [`set_strand_stats`](Self::set_strand_stats)
+    pub fn strand_counts(&self) -> Option<StrandCounts> {
+        self.scanner.strand_stats.then_some(self.tally.strands)
+    }
+
+    /// Sequences added and, of those, redundant ones (at least one k-mer, all of
+    /// them already solid), when tracked with [`set_track_redundancy`](Self::set_track_redundancy)
+    pub fn redundancy(&self) -> Option<(u64, u64)> {
+        self.redundancy
+    }
+
+    /// Split the k-mer map into shards so `add_sequences` can insert from
+    /// `threads` workers at once. Must be set before any sequence is added.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.shards = if threads > 1 { (threads * 4).next_power_of_two() } else { 1 };
+        self.select_backend();
+    }
+
+    /// Width of the counts in the table of every possible key used up to `MAX_DENSE_K`,
+    /// `None` to pick the narrowest one holding the counts kept: 8 bits, half the memory,
+    /// unless `min_count` is over 255 or full counts are tracked. With 8 bits, counts stop
+    /// at 255 even when tracked. Hash maps always keep 16-bit counts, as their entries are
+    /// padded to the alignment of their keys anyway. Must be set before any sequence is added.
+    pub fn set_count_width(&mut self, width: Option<CountWidth>) {
+        self.count_width = width;
+        self.select_backend();
+    }
+
+    /// Width of the stored counts: the dense table's, or 16 bits in a hash map.
+    /// `None` in sketch modes, which keep no per-k-mer counts.
+    pub fn count_width(&self) -> Option<CountWidth> {
+        match &self.kmers {
+            KmerMap::Dense(dense) => Some(dense.width()),
+            KmerMap::Estimate(_) | KmerMap::CountMin(_) => None,
+            _ => Some(CountWidth::U16),
+        }
+    }
+
+    /// Count exact k-mers above `MAX_DENSE_K` in one concurrent hash map (DashMap):
+    /// with several threads, each takes a share of the sequences added and inserts
+    /// its k-mers directly, instead of the k-mers being bucketed by shard first and
+    /// every shard updated by one thread. Must be set before any sequence is added.
+    pub fn set_concurrent_map(&mut self, concurrent: bool) {
+        self.concurrent = concurrent;
+        self.select_backend();
+    }
+
+    /// Estimate the distinct count with a HyperLogLog of `2^precision`
+    /// registers instead of storing k-mers. Solid k-mers are not tracked in
+    /// this mode. Must be set before any sequence is added.
+    pub fn set_estimate(&mut self, precision: Option<u8>) {
+        self.estimate_precision = precision;
+        self.select_backend();
+    }
+
+    /// Keep memory bounded to roughly `bytes` by replacing the exact map with
+    /// a Count-Min sketch (solid k-mers) and a HyperLogLog (distinct k-mers).
+    /// Takes precedence over `set_estimate`. Must be set before any sequence is added.
+    pub fn set_max_memory(&mut self, bytes: Option<usize>) {
+        self.max_memory = bytes;
+        self.select_backend();
+    }
+
+    /// Feed the estimate and Count-Min sketches the rolling ntHash of each
+    /// window, so k-mers are never materialized and k is not limited to 32.
+    /// Exact counting, ambiguous bases, case-sensitive keys, spaced seeds and
+    /// minimizers keep hashing the k-mers themselves. Must be set before any
+    /// sequence is added.
+    pub fn set_nthash(&mut self, nthash: bool) {
+        self.nthash = nthash;
+        self.select_backend();
+    }
+
+    /// Relative error of `distinct_kmers` when it is estimated, `None` when counting exactly
+    pub fn estimate_error(&self) -> Option<f64> {
+        match &self.kmers {
+            KmerMap::Estimate(hll) => Some(hll.relative_error()),
+            KmerMap::CountMin(state) => Some(state.distinct.relative_error()),
+            _ => None,
+        }
+    }
+
+    /// The Count-Min sketch in bounded-memory mode
+    pub fn count_min(&self) -> Option<&CountMinSketch> {
+        match &self.kmers {
+            KmerMap::CountMin(state) => Some(&state.sketch),
+            _ => None,
+        }
+    }
+
+    /// Skip windows whose dinucleotide entropy is below `bits` (0 for a
+    /// homopolymer, 1 for a dinucleotide repeat, at most 4), a DUST-like filter
+    /// for simple repeats. Must be set before any sequence is added.
+    pub fn set_min_complexity(&mut self, bits: Option<f64>) {
+        self.scanner.min_entropy = bits;
+    }
+
+    /// Skip windows holding a run of the same base longer than `max`, such as
+    /// poly-A tails. Must be set before any sequence is added.
+    pub fn set_max_homopolymer(&mut self, max: Option<usize>) {
+        assert!(max != Some(0), "a homopolymer limit must allow at least one base");
+        self.scanner.max_homopolymer = max;
+    }
+
+    /// Skip windows whose share of G and C bases lies outside `min..=max`, both
+    /// fractions, e.g. to set organelle sequence apart from nuclear sequence. Must be
+    /// set before any sequence is added.
+    pub fn set_gc_range(&mut self, range: Option<(f64, f64)>) {
+        assert!(range.is_none_or(|(min, max)| min <= max), "a GC range must not be empty");
+        self.scanner.gc_range = range;
+    }
+
+    /// Also keep a bottom-k MinHash sketch of the `size` smallest k-mer hashes,
+    /// whatever the counting mode. Must be set before any sequence is added.
+    pub fn set_minhash_size(&mut self, size: Option<usize>) {
+        self.minhash = size.map(MinHash::new);
+    }
+
+    /// Seed the hashes of the estimate, Count-Min and MinHash sketches, [`MINHASH_SEED`] by
+    /// default. Sketches are only comparable or mergeable under the same seed; a different
+    /// seed gives estimates with independent errors. Exact counts do not depend on it. Must be
+    /// set before any sequence is added.
+    pub fn set_hash_seed(&mut self, seed: u32) {
+        self.scanner.hash_seed = seed;
+        self.scanner.hash_salt = mix64(seed as u64);
+    }
+
+    pub fn hash_seed(&self) -> u32 {
+        self.scanner.hash_seed
+    }
+
+    /// The MinHash sketch, if one is kept. Its hashes are [`murmur3_64`] with
+    /// the [hash seed](Self::set_hash_seed) of the canonical k-mer bytes (forward with
+    /// `set_forward_only`, only the seed positions with a spaced seed), uppercase
+    /// unless case-sensitive and with `U` in RNA mode.
+    pub fn minhash(&self) -> Option<&MinHash> {
+        self.minhash.as_ref()
+    }
+
+    /// Most frequent k-mers by estimated count in bounded-memory mode, highest first
+    pub fn heavy_hitters(&self) -> Vec<HeavyHitter> {
+        match &self.kmers {
+            KmerMap::CountMin(state) => state.heavy.sorted(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn k(&self) -> usize {
+        self.scanner.k
+    }
+
+    pub fn is_forward_only(&self) -> bool {
+        !self.scanner.canonical
+    }
+
+    pub fn is_rna(&self) -> bool {
+        self.scanner.rna
+    }
+
+    pub fn is_protein(&self) -> bool {
+        self.scanner.protein
+    }
+
+    /// Whether k-mers are stored 2-bit packed in a `u64`
+    pub fn is_packed(&self) -> bool {
+        self.scanner.packed
+    }
+
+    /// Count every k-mer window of `seq`
+    pub fn add_sequence(&mut self, seq: &[u8]) {
+        let scanner = &self.scanner;
+        let mut solid = 0;
+        // Whether the sequence has k-mers, and whether all of them were solid before it
+        let (mut any, mut covered) = (false, self.redundancy.is_some());
+        let min_count = scanner.min_count;
+        let skipped = match &mut self.kmers {
+            KmerMap::Dense(dense) => scanner.scan(seq, |_, key| {
+                any = true;
+                covered = covered && dense.count(key) >= min_count;
+                solid += dense.observe(key, min_count, scanner.saturate_at) as u64;
+            }),
+            KmerMap::Packed(shards) => scanner.scan(seq, |_, key| {
+                let shard = shard_of(&key, shards.len());
+                let shard = &mut shards[shard];
+                any = true;
+                covered = covered && shard.get(&key).is_some_and(|&count| count >= min_count);
+                solid += observe(shard, key, min_count, scanner.saturate_at) as u64;
+            }),
+            KmerMap::Bytes(shards) => {
+                let key = &mut self.scratch;
+                scanner.scan(seq, |start, _| {
+                    scanner.bytes_key_into(seq, start, key);
+                    let shard = shard_of(key, shards.len());
+                    let shard = &mut shards[shard];
+                    any = true;
+                    covered = covered && shard.get(key).is_some_and(|&count| count >= min_count);
+                    solid += observe_slice(shard, key, min_count, scanner.saturate_at) as u64;
+                })
+            }
+            KmerMap::ConcurrentPacked(map) => scanner.scan(seq, |_, key| {
+                any = true;
+                covered = covered && map.get(&key).is_some_and(|count| *count >= min_count);
+                solid += observe_shared(map, key, min_count, scanner.saturate_at) as u64;
+            }),
+            KmerMap::ConcurrentBytes(map) => {
+                let key = &mut self.scratch;
+                scanner.scan(seq, |start, _| {
+                    scanner.bytes_key_into(seq, start, key);
+                    any = true;
+                    covered = covered && map.get(key.as_slice()).is_some_and(|count| *count >= min_count);
+                    solid += observe_shared_slice(map, key, min_count, scanner.saturate_at) as u64;
+                })
+            }
+            KmerMap::Estimate(hll) => scanner.scan_into_sketch(seq, hll),
+            KmerMap::CountMin(state) => {
+                let (new_solid, skipped) = scanner.scan_into_count_min(seq, state);
+                solid += new_solid;
+                skipped
+            }
+        };
+        if let Some(minhash) = &mut self.minhash {
+            scanner.scan_into_minhash(seq, minhash, &mut self.scratch);
+        }
+        if let Some((sequences, redundant)) = &mut self.redundancy {
+            *sequences += 1;
+            *redundant += (any && covered) as u64;
+        }
+        self.solid_kmers += solid;
+        self.tally += skipped;
+    }
+
+    /// Count a batch of sequences, extracting and inserting k-mers across the rayon thread pool
+    pub fn add_sequences<S: AsRef<[u8]> + Sync>(&mut self, seqs: &[S]) {
+        // Solidity in the Count-Min sketch, and redundancy, depend on insertion order, so they stay sequential
+        if self.shards == 1 || matches!(self.kmers, KmerMap::CountMin(_)) || self.redundancy.is_some() {
+            for seq in seqs {
+                self.add_sequence(seq.as_ref());
+            }
+            return;
+        }
+
+        let scanner = &self.scanner;
+        let shards = self.shards;
+        let (solid, skipped) = match &mut self.kmers {
+            KmerMap::Dense(dense) => {
+                let bits = dense.range_bits(shards);
+                let (buckets, skipped) = bucketed(seqs, dense.ranges(bits), |seq, buckets| {
+                    scanner.scan(seq, |_, key| buckets[(key >> bits) as usize].push(key))
+                });
+                (dense.observe_buckets(buckets, bits, scanner.min_count, scanner.saturate_at), skipped)
+            }
+            KmerMap::Packed(maps) => {
+                let (buckets, skipped) = bucketed(seqs, shards, |seq, buckets| {
+                    scanner.scan(seq, |_, key| buckets[shard_of(&key, shards)].push(key))
+                });
+                (observe_buckets(maps, buckets, scanner.min_count, scanner.saturate_at), skipped)
+            }
+            KmerMap::Bytes(maps) => {
+                let (buckets, skipped) = bucketed(seqs, shards, |seq, buckets| {
+                    scanner.scan(seq, |start, _| {
+                        let key = scanner.bytes_key(seq, start);
+                        buckets[shard_of(&key, shards)].push(key);
+                    })
+                });
+                (observe_buckets(maps, buckets, scanner.min_count, scanner.saturate_at), skipped)
+            }
+            KmerMap::ConcurrentPacked(map) => seqs
+                .par_iter()
+                .map(|seq| {
+                    let mut solid = 0;
+                    let skipped = scanner.scan(seq.as_ref(), |_, key| {
+                        solid += observe_shared(map, key, scanner.min_count, scanner.saturate_at) as u64;
+                    });
+                    (solid, skipped)
+                })
+                .reduce(|| (0, ScanTally::default()), |(a, sa), (b, sb)| (a + b, sa + sb)),
+            KmerMap::ConcurrentBytes(map) => seqs
+                .par_iter()
+                .map_init(Vec::new, |key, seq| {
+                    let seq = seq.as_ref();
+                    let mut solid = 0;
+                    let skipped = scanner.scan(seq, |start, _| {
+                        scanner.bytes_key_into(seq, start, key);
+                        solid += observe_shared_slice(map, key, scanner.min_count, scanner.saturate_at) as u64;
+                    });
+                    (solid, skipped)
+                })
+                .reduce(|| (0, ScanTally::default()), |(a, sa), (b, sb)| (a + b, sa + sb)),
+            KmerMap::Estimate(hll) => {
+                let chunk_size = seqs.len().div_ceil(rayon::current_num_threads()).max(1);
+                let precision = hll.precision();
+                let (sketch, skipped) = seqs
+                    .par_chunks(chunk_size)
+                    .map(|chunk| {
+                        let mut local = HyperLogLog::new(precision);
+                        let skipped = chunk
+                            .iter()
+                            .map(|seq| scanner.scan_into_sketch(seq.as_ref(), &mut local))
+                            .sum::<ScanTally>();
+                        (local, skipped)
+                    })
+                    .reduce(
+                        || (HyperLogLog::new(precision), ScanTally::default()),
+                        |(mut a, sa), (b, sb)| {
+                            a.merge(&b);
+                            (a, sa + sb)
+                        },
+                    );
+                hll.merge(&sketch);
+                (0, skipped)
+            }
+            KmerMap::CountMin(_) => unreachable!("Count-Min counting is sequential"),
+        };
+        if let Some(minhash) = &mut self.minhash {
+            let chunk_size = seqs.len().div_ceil(rayon::current_num_threads()).max(1);
+            let size = minhash.size();
+            let sketch = seqs
+                .par_chunks(chunk_size)
+                .map(|chunk| {
+                    let mut local = MinHash::new(size);
+                    let mut key = Vec::with_capacity(scanner.key_len());
+                    for seq in chunk {
+                        scanner.scan_into_minhash(seq.as_ref(), &mut local, &mut key);
+                    }
+                    local
+                })
+                .reduce(
+                    || MinHash::new(size),
+                    |mut a, b| {
+                        a.merge(&b);
+                        a
+                    },
+                );
+            minhash.merge(&sketch);
+        }
+        self.solid_kmers += solid;
+        self.tally += skipped;
+    }
+
+    /// Number of distinct canonical k-mers seen at least `min_count` times
+    pub fn unique_solid_kmers(&self) -> u64 {
+        self.solid_kmers
+    }
+
+    /// Number of distinct canonical k-mers seen at least once (estimated in estimate mode)
+    pub fn distinct_kmers(&self) -> u64 {
+        self.kmers.len() as u64
+    }
+
+    /// Approximate memory held by the k-mer storage, in bytes. Allocator overhead
+    /// is not included, so the process uses somewhat more.
+    pub fn memory_bytes(&self) -> usize {
+        self.kmers.memory_bytes(self.scanner.key_len())
+    }
+
+    /// Upper estimate of `memory_bytes` at its peak while adding sequences with up to
+    /// `new_kmers` k-mers, counting hash tables that would grow at their old and new size
+    pub fn peak_memory_bytes(&self, new_kmers: usize) -> usize {
+        self.kmers.peak_bytes(self.scanner.key_len(), new_kmers)
+    }
+
+    /// Number of k-mers solid in both this counter and `other`, e.g. for the
+    /// Jaccard index of two samples. Both must count exactly, with the same k-mers
+    /// (k, strand mode, spaced seed and key representation).
+    pub fn shared_solid_kmers(&self, other: &KmerCounter) -> u64 {
+        assert!(
+            self.scanner.k == other.scanner.k
+                && self.scanner.canonical == other.scanner.canonical
+                && self.scanner.seed == other.scanner.seed,
+            "shared k-mers need counters of the same k-mers"
+        );
+        let (min_count, other_min_count) = (self.scanner.min_count, other.scanner.min_count);
+        match (&self.kmers, &other.kmers) {
+            (KmerMap::Dense(a), KmerMap::Dense(b)) => a.shared_solid(b, min_count, other_min_count),
+            (KmerMap::Packed(a), KmerMap::Packed(b)) => shared_solid(a, b, min_count, other_min_count),
+            (KmerMap::Bytes(a), KmerMap::Bytes(b)) => shared_solid(a, b, min_count, other_min_count),
+            (KmerMap::ConcurrentPacked(a), KmerMap::ConcurrentPacked(b)) => {
+                shared_solid_concurrent(a, b, min_count, other_min_count)
+            }
+            (KmerMap::ConcurrentBytes(a), KmerMap::ConcurrentBytes(b)) => {
+                shared_solid_concurrent(a, b, min_count, other_min_count)
+            }
+            _ => panic!("shared k-mers need two exact counters with the same key representation"),
+        }
+    }
+
+    /// Call `f(kmer, count)` for every solid canonical k-mer, in map order.
+    /// Does nothing in the sketch modes, which keep no keys.
+    pub fn for_each_solid_kmer(&self, f: impl FnMut(&[u8], u16)) {
+        self.for_each_kmer_from(self.scanner.min_count, f)
+    }
+
+    /// Number of distinct solid k-mers that are their own reverse complement.
+    /// Always 0 for odd k and in the sketch modes.
+    pub fn palindromic_solid_kmers(&self) -> u64 {
+        if self.scanner.key_len() % 2 == 1 {
+            return 0;
+        }
+        let mut palindromes = 0;
+        self.for_each_solid_kmer(|kmer, _| {
+            if is_palindrome(kmer, self.scanner.rna) {
+                palindromes += 1;
+            }
+        });
+        palindromes
+    }
+
+    /// The `n` most frequent k-mers with their counts, highest first and ties in
+    /// byte order. A bounded min-heap keeps the map from being sorted, and a k-mer
+    /// is only copied when it beats the least frequent one kept. Counts are capped
+    /// at the saturation point unless `set_track_counts` is on; empty in the sketch modes.
+    pub fn top_kmers(&self, n: usize) -> Vec<(Vec<u8>, u16)> {
+        if n == 0 {
+            return Vec::new();
+        }
+        let mut heap: BinaryHeap<Reverse<(u16, Reverse<Vec<u8>>)>> = BinaryHeap::with_capacity(n);
+        self.for_each_kmer_from(1, |kmer, count| {
+            if heap.len() == n {
+                let Reverse((least, Reverse(last))) = heap.peek().expect("the heap is full");
+                if (count, Reverse(kmer)) <= (*least, Reverse(last.as_slice())) {
+                    return;
+                }
+                heap.pop();
+            }
+            heap.push(Reverse((count, Reverse(kmer.to_vec()))));
+        });
+        heap.into_sorted_vec().into_iter().map(|Reverse((count, Reverse(kmer)))| (kmer, count)).collect()
+    }
+
+    fn for_each_kmer_from(&self, min_count: u16, mut f: impl FnMut(&[u8], u16)) {
+        match &self.kmers {
+            KmerMap::Dense(dense) => {
+                let mut kmer = Vec::with_capacity(self.scanner.key_len());
+                for (packed, count) in dense.iter().filter(|&(_, count)| count >= min_count) {
+                    unpack_kmer_into(packed, self.scanner.key_len(), self.scanner.rna, &mut kmer);
+                    f(&kmer, count);
+                }
+            }
+            KmerMap::Packed(shards) => {
+                let mut kmer = Vec::with_capacity(self.scanner.key_len());
+                for (&packed, &count) in shards.iter().flatten() {
+                    if count >= min_count {
+                        unpack_kmer_into(packed, self.scanner.key_len(), self.scanner.rna, &mut kmer);
+                        f(&kmer, count);
+                    }
+                }
+            }
+            KmerMap::Bytes(shards) => {
+                for (kmer, &count) in shards.iter().flatten() {
+                    if count >= min_count {
+                        f(kmer, count);
+                    }
+                }
+            }
+            KmerMap::ConcurrentPacked(map) => {
+                let mut kmer = Vec::with_capacity(self.scanner.key_len());
+                for entry in map.iter().filter(|entry| *entry.value() >= min_count) {
+                    unpack_kmer_into(*entry.key(), self.scanner.key_len(), self.scanner.rna, &mut kmer);
+                    f(&kmer, *entry.value());
+                }
+            }
+            KmerMap::ConcurrentBytes(map) => {
+                for entry in map.iter().filter(|entry| *entry.value() >= min_count) {
+                    f(entry.key(), *entry.value());
+                }
+            }
+            KmerMap::Estimate(_) | KmerMap::CountMin(_) => {}
+        }
+    }
+
+    /// k-mer spectrum: `(abundance, number of distinct k-mers)` pairs in
+    /// increasing abundance, skipping empty bins. Counts are capped at the
+    /// saturation point, so without `set_track_counts` the last bin is
+    /// "at least `min_count`". Empty in the sketch modes.
+    pub fn histogram(&self) -> Vec<(u16, u64)> {
+        let mut bins = vec![0u64; self.scanner.saturate_at as usize + 1];
+        match &self.kmers {
+            KmerMap::Dense(dense) => dense.iter().for_each(|(_, c)| bins[c as usize] += 1),
+            KmerMap::Packed(shards) => shards.iter().flat_map(|m| m.values()).for_each(|&c| bins[c as usize] += 1),
+            KmerMap::Bytes(shards) => shards.iter().flat_map(|m| m.values()).for_each(|&c| bins[c as usize] += 1),
+            KmerMap::ConcurrentPacked(map) => map.iter().for_each(|entry| bins[*entry.value() as usize] += 1),
+            KmerMap::ConcurrentBytes(map) => map.iter().for_each(|entry| bins[*entry.value() as usize] += 1),
+            KmerMap::Estimate(_) | KmerMap::CountMin(_) => return Vec::new(),
+        }
+        bins.into_iter()
+            .enumerate()
+            .filter(|&(_, n)| n > 0)
+            .map(|(count, n)| (count as u16, n))
+            .collect()
+    }
+
+    /// Number of k-mer positions counted, repeats included: the windows left after the
+    /// ambiguous-base, homopolymer, low-complexity and GC filters. With minimizers, this is
+    /// every such window, not only the minimizers picked from them.
+    pub fn total_kmers(&self) -> u64 {
+        self.tally.counted
+    }
+
+    /// Number of windows skipped for containing an ambiguous base
+    pub fn skipped_kmers(&self) -> u64 {
+        self.tally.ambiguous
+    }
+
+    /// Number of windows skipped for a homopolymer longer than `set_max_homopolymer`
+    pub fn homopolymer_kmers(&self) -> u64 {
+        self.tally.homopolymer
+    }
+
+    /// Number of windows masked as low-complexity (see `set_min_complexity`)
+    pub fn low_complexity_kmers(&self) -> u64 {
+        self.tally.low_complexity
+    }
+
+    /// Number of windows skipped for a GC fraction outside `set_gc_range`
+    pub fn gc_kmers(&self) -> u64 {
+        self.tally.gc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reproducible reads over a small genome, so most k-mers recur, with a few Ns
+    fn reads() -> Vec<Vec<u8>> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let genome: Vec<u8> = (0..5000).map(|_| b"ACGT"[(next() % 4) as usize]).collect();
+        (0..2000)
+            .map(|_| {
+                let start = (next() % (genome.len() as u64 - 100)) as usize;
+                let mut read = genome[start..start + 100].to_vec();
+                if next() % 10 == 0 {
+                    read[(next() % 100) as usize] = b'N';
+                }
+                read
+            })
+            .collect()
+    }
+
+    /// What a counter reports, to compare backends
+    #[derive(Debug, PartialEq)]
+    struct Counts {
+        distinct: u64,
+        solid: u64,
+        histogram: Vec<(u16, u64)>,
+        /// Every k-mer with its count, sorted
+        kmers: Vec<(Vec<u8>, u16)>,
+    }
+
+    fn counts(counter: &KmerCounter) -> Counts {
+        let mut kmers = Vec::new();
+        counter.for_each_kmer_from(1, |kmer, count| kmers.push((kmer.to_vec(), count)));
+        kmers.sort_unstable();
+        Counts {
+            distinct: counter.distinct_kmers(),
+            solid: counter.unique_solid_kmers(),
+            histogram: counter.histogram(),
+            kmers,
+        }
+    }
+
+    /// `counts` as a table whose counts stop at `max` would report them
+    fn saturated(mut counts: Counts, max: u16) -> Counts {
+        let over: u64 = counts.histogram.iter().filter(|&&(count, _)| count >= max).map(|&(_, n)| n).sum();
+        counts.histogram.retain(|&(count, _)| count < max);
+        if over > 0 {
+            counts.histogram.push((max, over));
+        }
+        for (_, count) in &mut counts.kmers {
+            *count = (*count).min(max);
+        }
+        counts
+    }
+
+    #[test]
+    fn dense_table_counts_as_the_packed_map() {
+        let reads = reads();
+        for k in [5, 11, MAX_DENSE_K] {
+            for threads in [1, 4] {
+                for width in [CountWidth::U8, CountWidth::U16] {
+                    let mut dense = KmerCounter::new(k);
+                    dense.set_threads(threads);
+                    dense.set_track_counts(true);
+                    dense.set_count_width(Some(width));
+                    assert!(matches!(dense.kmers, KmerMap::Dense(_)));
+                    assert_eq!(dense.count_width(), Some(width));
+                    // Same settings and packed keys, stored in hash maps instead
+                    let mut mapped = KmerCounter::new(k);
+                    mapped.set_threads(threads);
+                    mapped.set_track_counts(true);
+                    mapped.kmers = KmerMap::Packed((0..mapped.shards).map(|_| FxHashMap::default()).collect());
+
+                    for batch in reads.chunks(500) {
+                        dense.add_sequences(batch);
+                        mapped.add_sequences(batch);
+                    }
+                    let context = format!("k = {}, {} threads, {:?} cells", k, threads, width);
+                    assert!(dense.unique_solid_kmers() > 0, "{}", context);
+                    let (dense, mapped) = (counts(&dense), counts(&mapped));
+                    // 8-bit cells stop at 255, which only the distinct and solid k-mers ignore
+                    assert_eq!((dense.distinct, dense.solid), (mapped.distinct, mapped.solid), "{}", context);
+                    assert_eq!(dense, saturated(mapped, width.max_count()), "{}", context);
+                    if k == 5 && width == CountWidth::U8 {
+                        // Most 5-mers recur over 255 times, so their counts do stop there
+                        let top = dense.histogram.last().copied().unwrap_or_default();
+                        assert!(top.0 == 255 && top.1 > dense.distinct / 2, "{}: {:?}", context, top);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Low-complexity window masking, updated base by base as the window slides
+
+use super::base_code;
+
+/// Shannon entropy, in bits, of the dinucleotides in the current window:
+/// 0 for a homopolymer, 1 for an `ATAT...` repeat, at most 4. Ambiguous
+/// bases count as `T`.
+pub(super) struct DinucleotideEntropy {
+    k: usize,
+    counts: [u32; 16],
+    /// `c * log2(c)` for every possible count
+    table: Vec<f64>,
+}
+
+impl DinucleotideEntropy {
+    pub(super) fn new(k: usize) -> Self {
+        let table = (0..k).map(|c| if c > 1 { c as f64 * (c as f64).log2() } else { 0.0 }).collect();
+        DinucleotideEntropy { k, counts: [0; 16], table }
+    }
+
+    /// Entropy of the `k`-long `window`, computed from scratch
+    pub(super) fn of_window(window: &[u8]) -> f64 {
+        let mut entropy = DinucleotideEntropy::new(window.len());
+        for pair in window.windows(2) {
+            entropy.adjust(pair[0], pair[1], 1);
+        }
+        entropy.entropy()
+    }
+
+    /// Slide the window so it ends at `seq[end]`
+    pub(super) fn advance(&mut self, seq: &[u8], end: usize) {
+        if end >= 1 {
+            self.adjust(seq[end - 1], seq[end], 1);
+        }
+        if end >= self.k {
+            self.adjust(seq[end - self.k], seq[end - self.k + 1], -1);
+        }
+    }
+
+    fn adjust(&mut self, first: u8, second: u8, delta: i32) {
+        let count = &mut self.counts[(base_code(first) << 2 | base_code(second)) as usize];
+        *count = count.wrapping_add_signed(delta);
+    }
+
+    /// Entropy once the window is full; a window without dinucleotides (k = 1) is maximal.
+    /// Summed afresh from the counts, so equal windows always score the same.
+    pub(super) fn entropy(&self) -> f64 {
+        let pairs = (self.k - 1) as f64;
+        if pairs == 0.0 {
+            return f64::INFINITY;
+        }
+        let sum: f64 = self.counts.iter().map(|&count| self.table[count as usize]).sum();
+        pairs.log2() - sum / pairs
+    }
+}
+
+/// Tracks runs of one repeated base to tell whether the current window
+/// holds a homopolymer longer than `max`
+pub(super) struct HomopolymerRuns {
+    max: usize,
+    /// Length of the run of equal bases ending at the current position
+    run: usize,
+    /// Last position where such a run exceeded `max`
+    last_long: Option<usize>,
+}
+
+impl HomopolymerRuns {
+    pub(super) fn new(max: usize) -> Self {
+        HomopolymerRuns { max, run: 0, last_long: None }
+    }
+
+    /// Whether `window` has a run of the same base longer than `max`
+    pub(super) fn in_window(window: &[u8], max: usize) -> bool {
+        let mut runs = HomopolymerRuns::new(max);
+        (0..window.len()).for_each(|end| runs.advance(window, end));
+        runs.exceeded_since(0)
+    }
+
+    /// Extend the runs with `seq[end]`
+    pub(super) fn advance(&mut self, seq: &[u8], end: usize) {
+        if end > 0 && seq[end].eq_ignore_ascii_case(&seq[end - 1]) {
+            self.run += 1;
+        } else {
+            self.run = 1;
+        }
+        if self.run > self.max {
+            self.last_long = Some(end);
+        }
+    }
+
+    /// Whether a run longer than `max` lies entirely at or after `start`
+    pub(super) fn exceeded_since(&self, start: usize) -> bool {
+        self.last_long.is_some_and(|end| end >= start + self.max)
+    }
+}
+
+/// Counts the G and C bases of the current `k`-long window, to tell its GC fraction
+pub(super) struct GcWindow {
+    k: usize,
+    gc: usize,
+}
+
+impl GcWindow {
+    pub(super) fn new(k: usize) -> Self {
+        GcWindow { k, gc: 0 }
+    }
+
+    /// GC fraction of `window`, counted from scratch
+    pub(super) fn of_window(window: &[u8]) -> f64 {
+        let mut gc = GcWindow::new(window.len());
+        (0..window.len()).for_each(|end| gc.advance(window, end));
+        gc.fraction()
+    }
+
+    /// Slide the window so it ends at `seq[end]`
+    pub(super) fn advance(&mut self, seq: &[u8], end: usize) {
+        self.gc += is_gc(seq[end]) as usize;
+        if end >= self.k {
+            self.gc -= is_gc(seq[end - self.k]) as usize;
+        }
+    }
+
+    /// Share of the full window's bases that are G or C; other bases, ambiguous ones
+    /// included, count as neither
+    pub(super) fn fraction(&self) -> f64 {
+        self.gc as f64 / self.k as f64
+    }
+}
+
+fn is_gc(base: u8) -> bool {
+    matches!(base, b'G' | b'C' | b'g' | b'c')
+}
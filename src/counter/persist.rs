@@ -0,0 +1,211 @@
+//! Binary snapshot of a counter's k-mer map, for resuming a count with more reads.
+//!
+//! Layout (little-endian): magic `UKMC`, format version (u8), k (u32), key
+//! encoding (u8: 0 = 2-bit packed u64, 1 = k raw bytes), alphabet (u8: 0 = DNA,
+//! 1 = RNA, 2 = protein; only 0 and 1 before protein mode),
+//! forward-only flag (u8, since version 2; version 1 snapshots are canonical),
+//! count width in bytes (u8, since version 3; earlier snapshots use 2), entry
+//! count (u64), then each entry as its key followed by its count (u16).
+
+use std::error::Error;
+use std::io::{Read, Write};
+
+use super::{base_code, observe_count, observe_count_shared, shard_of, unpack_kmer, KmerCounter, KmerMap};
+
+const MAGIC: &[u8; 4] = b"UKMC";
+const VERSION: u8 = 3;
+/// Bytes of each stored count, `u16`
+const COUNT_WIDTH: u8 = 2;
+const PACKED: u8 = 0;
+const BYTES: u8 = 1;
+const RNA: u8 = 1;
+const PROTEIN: u8 = 2;
+
+fn read_array<const N: usize>(input: &mut impl Read) -> std::io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    input.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// 2-bit key of a snapshot entry, stored either packed or as bytes
+fn packed_key(bytes: Option<&Vec<u8>>, packed: u64) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let Some(kmer) = bytes else {
+        return Ok(packed);
+    };
+    if !kmer.iter().all(|&b| b"ACGTU".contains(&b)) {
+        return Err("k-mer snapshot holds ambiguous or lowercase bases; \
+                    load it with the same --allow-ambiguous/--case-sensitive settings"
+            .into());
+    }
+    Ok(kmer.iter().fold(0, |acc, &b| (acc << 2) | base_code(b)))
+}
+
+impl KmerCounter {
+    /// Serialize every stored k-mer with its count. Sketch modes keep no keys
+    /// and cannot be saved.
+    pub fn save(&self, out: &mut impl Write) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.scanner.seed.is_some() {
+            return Err("spaced-seed counters cannot be saved".into());
+        }
+        let encoding = match &self.kmers {
+            KmerMap::Dense(_) | KmerMap::Packed(_) | KmerMap::ConcurrentPacked(_) => PACKED,
+            KmerMap::Bytes(_) | KmerMap::ConcurrentBytes(_) => BYTES,
+            KmerMap::Estimate(_) | KmerMap::CountMin(_) => {
+                return Err("sketch-mode counters keep no k-mers and cannot be saved".into());
+            }
+        };
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        out.write_all(&(self.scanner.k as u32).to_le_bytes())?;
+        let alphabet = if self.scanner.protein { PROTEIN } else { self.scanner.rna as u8 };
+        out.write_all(&[encoding, alphabet, !self.scanner.canonical as u8, COUNT_WIDTH])?;
+        out.write_all(&(self.kmers.len() as u64).to_le_bytes())?;
+        match &self.kmers {
+            KmerMap::Dense(dense) => {
+                for (key, count) in dense.iter() {
+                    out.write_all(&key.to_le_bytes())?;
+                    out.write_all(&count.to_le_bytes())?;
+                }
+            }
+            KmerMap::Packed(shards) => {
+                for (key, count) in shards.iter().flatten() {
+                    out.write_all(&key.to_le_bytes())?;
+                    out.write_all(&count.to_le_bytes())?;
+                }
+            }
+            KmerMap::Bytes(shards) => {
+                for (key, count) in shards.iter().flatten() {
+                    out.write_all(key)?;
+                    out.write_all(&count.to_le_bytes())?;
+                }
+            }
+            KmerMap::ConcurrentPacked(map) => {
+                for entry in map.iter() {
+                    out.write_all(&entry.key().to_le_bytes())?;
+                    out.write_all(&entry.value().to_le_bytes())?;
+                }
+            }
+            KmerMap::ConcurrentBytes(map) => {
+                for entry in map.iter() {
+                    out.write_all(entry.key())?;
+                    out.write_all(&entry.value().to_le_bytes())?;
+                }
+            }
+            KmerMap::Estimate(_) | KmerMap::CountMin(_) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Add the k-mers and counts of a snapshot written by `save`. The snapshot
+    /// must use the same k; keys are converted if it used the other encoding,
+    /// and an empty counter switches to the snapshot's RNA mode.
+    pub fn load(&mut self, input: &mut impl Read) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.scanner.seed.is_some() {
+            return Err("cannot load a k-mer snapshot into a spaced-seed counter".into());
+        }
+        if &read_array::<4>(input)? != MAGIC {
+            return Err("not a k-mer snapshot (bad magic)".into());
+        }
+        let version = read_array::<1>(input)?[0];
+        if !(1..=VERSION).contains(&version) {
+            return Err(format!("unsupported k-mer snapshot version {}", version).into());
+        }
+        let k = u32::from_le_bytes(read_array(input)?) as usize;
+        if k != self.scanner.k {
+            return Err(format!("k-mer snapshot was built with k = {}, but --k is {}", k, self.scanner.k).into());
+        }
+        let [encoding, alphabet] = read_array::<2>(input)?;
+        if (alphabet == PROTEIN) != self.scanner.protein {
+            let residues = |protein| if protein { "protein" } else { "nucleotide" };
+            return Err(format!(
+                "k-mer snapshot holds {} k-mers, but this run counts {} k-mers (see --protein)",
+                residues(alphabet == PROTEIN),
+                residues(self.scanner.protein)
+            )
+            .into());
+        }
+        let rna = alphabet == RNA;
+        if rna != self.scanner.rna {
+            if self.kmers.len() > 0 {
+                return Err("k-mer snapshot and counter disagree on RNA mode".into());
+            }
+            self.set_rna(rna);
+        }
+        let forward_only = version >= 2 && read_array::<1>(input)?[0] != 0;
+        if forward_only != self.is_forward_only() {
+            let strand = |forward_only| if forward_only { "forward-only" } else { "canonical" };
+            return Err(format!(
+                "k-mer snapshot holds {} k-mers, but this run counts {} k-mers (see --forward-only)",
+                strand(forward_only),
+                strand(self.is_forward_only())
+            )
+            .into());
+        }
+        let count_width = if version >= 3 { read_array::<1>(input)?[0] } else { COUNT_WIDTH };
+        if count_width != COUNT_WIDTH {
+            return Err(format!(
+                "k-mer snapshot stores {}-byte counts, but this run counts with {}-byte counts",
+                count_width, COUNT_WIDTH
+            )
+            .into());
+        }
+        let entries = u64::from_le_bytes(read_array(input)?);
+
+        let (min_count, saturate_at) = (self.scanner.min_count, self.scanner.saturate_at);
+        let mut kmer = vec![0; k];
+        for _ in 0..entries {
+            let (packed, bytes) = match encoding {
+                PACKED => {
+                    let packed = u64::from_le_bytes(read_array(input)?);
+                    (packed, None)
+                }
+                BYTES => {
+                    input.read_exact(&mut kmer)?;
+                    (0, Some(&kmer))
+                }
+                other => return Err(format!("unknown k-mer key encoding {}", other).into()),
+            };
+            let count = u16::from_le_bytes(read_array(input)?);
+
+            let became_solid = match &mut self.kmers {
+                KmerMap::Dense(dense) => {
+                    let key = packed_key(bytes, packed)?;
+                    if key as usize >= dense.keys() {
+                        return Err(format!("k-mer snapshot holds a key out of range for k = {}", k).into());
+                    }
+                    dense.observe_count(key, count, min_count, saturate_at)
+                }
+                KmerMap::Packed(shards) => {
+                    let key = packed_key(bytes, packed)?;
+                    let shard = shard_of(&key, shards.len());
+                    observe_count(&mut shards[shard], key, count, min_count, saturate_at)
+                }
+                KmerMap::Bytes(shards) => {
+                    let key = match bytes {
+                        Some(kmer) => kmer.clone(),
+                        None => unpack_kmer(packed, k, rna),
+                    };
+                    let shard = shard_of(&key, shards.len());
+                    observe_count(&mut shards[shard], key, count, min_count, saturate_at)
+                }
+                KmerMap::ConcurrentPacked(map) => {
+                    observe_count_shared(map, packed_key(bytes, packed)?, count, min_count, saturate_at)
+                }
+                KmerMap::ConcurrentBytes(map) => {
+                    let key = match bytes {
+                        Some(kmer) => kmer.clone(),
+                        None => unpack_kmer(packed, k, rna),
+                    };
+                    observe_count_shared(map, key, count, min_count, saturate_at)
+                }
+                KmerMap::Estimate(_) | KmerMap::CountMin(_) => {
+                    return Err("cannot load a k-mer snapshot into a sketch-mode counter".into());
+                }
+            };
+            if became_solid {
+                self.solid_kmers += 1;
+            }
+        }
+        Ok(())
+    }
+}
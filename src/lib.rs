@@ -0,0 +1,174 @@
+//! Streaming canonical k-mer counting over FASTA/FASTQ reads.
+
+mod adapters;
+mod counter;
+pub mod nthash;
+mod reader;
+mod saturation;
+mod seq_stats;
+pub mod sketch;
+mod spectrum;
+
+pub use adapters::Adapters;
+pub use counter::{CountWidth, KmerCounter, StrandCounts, DEFAULT_MIN_COUNT, MAX_PACKED_K};
+pub use reader::{
+    open_mapped, open_mapped_with_capacity, open_reader, open_reader_with_capacity, FileRecordReader, MateCountError,
+    PairedReader, RecordError, RecordReader, SeqRecord, DEFAULT_BUFFER_SIZE, PHRED64_OFFSET, PHRED_OFFSET, STDIN_PATH,
+};
+pub use saturation::{Sample, SaturationFit, SaturationTracker, DEFAULT_HISTORY_WINDOW};
+pub use seq_stats::{NonAcgt, SeqStats};
+pub use spectrum::{estimate_genome_size, spectrum_svg, GenomeEstimate};
+
+use std::cmp::Ordering;
+
+/// Complement of every byte for DNA; bytes other than A/C/G/T/U (either case) map to themselves
+const DNA_COMPLEMENT: [u8; 256] = complement_table(false);
+/// Same as [`DNA_COMPLEMENT`] but `A` pairs with `U`
+const RNA_COMPLEMENT: [u8; 256] = complement_table(true);
+
+const fn complement_table(rna: bool) -> [u8; 256] {
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = i as u8;
+        i += 1;
+    }
+    let a = if rna { b'U' } else { b'T' };
+    table[b'A' as usize] = a;
+    table[b'a' as usize] = a.to_ascii_lowercase();
+    table[b'T' as usize] = b'A';
+    table[b't' as usize] = b'a';
+    table[b'U' as usize] = b'A';
+    table[b'u' as usize] = b'a';
+    table[b'C' as usize] = b'G';
+    table[b'c' as usize] = b'g';
+    table[b'G' as usize] = b'C';
+    table[b'g' as usize] = b'c';
+    table
+}
+
+/// Complement of a single base. In RNA mode `A` pairs with `U` instead of `T`.
+#[inline]
+pub fn complement(base: u8, rna: bool) -> u8 {
+    let table = if rna { &RNA_COMPLEMENT } else { &DNA_COMPLEMENT };
+    table[base as usize]
+}
+
+/// Fast reverse complement for &[u8]
+pub fn reverse_complement(kmer: &[u8], rna: bool) -> Vec<u8> {
+    let table = if rna { &RNA_COMPLEMENT } else { &DNA_COMPLEMENT };
+    kmer.iter().rev().map(|&c| table[c as usize]).collect()
+}
+
+/// Whether a base is an unambiguous nucleotide (A/C/G/T, or A/C/G/U for RNA), in either case
+pub fn is_unambiguous(base: u8, rna: bool) -> bool {
+    match base.to_ascii_uppercase() {
+        b'A' | b'C' | b'G' => true,
+        b'T' => !rna,
+        b'U' => rna,
+        _ => false,
+    }
+}
+
+/// Whether a residue is one of the 20 standard amino acids, in either case
+pub fn is_amino_acid(residue: u8) -> bool {
+    matches!(
+        residue.to_ascii_uppercase(),
+        b'A' | b'C' | b'D' | b'E' | b'F' | b'G' | b'H' | b'I' | b'K' | b'L'
+            | b'M' | b'N' | b'P' | b'Q' | b'R' | b'S' | b'T' | b'V' | b'W' | b'Y'
+    )
+}
+
+/// Guess whether a sequence is RNA: it contains `U` but no `T`
+pub fn looks_like_rna(sequence: &[u8]) -> bool {
+    let has = |b: u8| sequence.iter().any(|c| c.to_ascii_uppercase() == b);
+    has(b'U') && !has(b'T')
+}
+
+/// Whether a k-mer equals its own reverse complement, as many restriction sites do.
+/// Only even-length k-mers can be.
+pub fn is_palindrome(kmer: &[u8], rna: bool) -> bool {
+    let table = if rna { &RNA_COMPLEMENT } else { &DNA_COMPLEMENT };
+    kmer.iter().copied().eq(kmer.iter().rev().map(|&c| table[c as usize]))
+}
+
+/// Return the canonical k-mer (lexicographically smallest between kmer and its reverse complement)
+pub fn canonical_kmer(kmer: &[u8], rna: bool) -> Vec<u8> {
+    let mut canonical = kmer.to_vec();
+    canonicalize(&mut canonical, rna);
+    canonical
+}
+
+/// How a k-mer compares to its reverse complement: `Less` when the k-mer is its own
+/// canonical form, `Greater` when its reverse complement is, `Equal` for a palindrome
+pub fn strand_order(kmer: &[u8], rna: bool) -> Ordering {
+    let table = if rna { &RNA_COMPLEMENT } else { &DNA_COMPLEMENT };
+    // Compare against the reverse complement base by base, stopping at the first difference
+    let reverse = kmer.iter().rev().map(|&c| table[c as usize]);
+    kmer.iter().copied().cmp(reverse)
+}
+
+/// Replace a k-mer by its canonical form in place, without allocating
+pub fn canonicalize(kmer: &mut [u8], rna: bool) {
+    let table = if rna { &RNA_COMPLEMENT } else { &DNA_COMPLEMENT };
+    if strand_order(kmer, rna).is_le() {
+        return;
+    }
+    kmer.reverse();
+    for base in kmer.iter_mut() {
+        *base = table[*base as usize];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rna_pairs_u_with_a() {
+        assert_eq!(reverse_complement(b"AUCG", true), b"CGAU");
+        assert_eq!(reverse_complement(b"aucg", true), b"cgau");
+        assert_eq!(canonical_kmer(b"CGAU", true), b"AUCG");
+        assert_eq!(canonical_kmer(b"AUCG", true), b"AUCG");
+        // U pairs with A in DNA mode too; only what A pairs with changes
+        assert_eq!(reverse_complement(b"AUCG", false), b"CGAT");
+    }
+
+    /// The per-byte `match` the complement tables replaced
+    fn complement_by_match(base: u8, rna: bool) -> u8 {
+        match base {
+            b'A' if rna => b'U',
+            b'a' if rna => b'u',
+            b'A' => b'T',
+            b'T' => b'A',
+            b'U' => b'A',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'a' => b't',
+            b't' => b'a',
+            b'u' => b'a',
+            b'c' => b'g',
+            b'g' => b'c',
+            _ => base,
+        }
+    }
+
+    #[test]
+    fn complement_tables_match_the_old_match() {
+        for rna in [false, true] {
+            for base in 0..=u8::MAX {
+                assert_eq!(complement(base, rna), complement_by_match(base, rna), "byte {} (rna: {})", base, rna);
+            }
+        }
+    }
+
+    #[test]
+    fn rna_is_detected_from_u_without_t() {
+        assert!(looks_like_rna(b"ACGUACGU"));
+        assert!(looks_like_rna(b"acguNN"));
+        assert!(!looks_like_rna(b"ACGTACGT"));
+        assert!(!looks_like_rna(b"ACGUACGT"));
+        assert!(!looks_like_rna(b"ACGNACGN"));
+        assert!(!looks_like_rna(b""));
+    }
+}
@@ -1,225 +1,3396 @@
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, ValueEnum};
 use futures::{SinkExt, StreamExt};
-use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex};
+use rayon::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rustc_hash::FxHashSet;
+use serde::Serialize;
+use std::fmt;
+use std::fs::File;
+use std::net::{IpAddr, SocketAddr};
+use std::io::{BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, info, info_span, trace, warn, Instrument};
+use tracing_subscriber::EnvFilter;
 use warp::ws::{Message, WebSocket};
 use warp::Filter;
-use flate2::read::MultiGzDecoder;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read};
-use bio::io::{fasta, fastq};
+use unique_kmers_evolution::{
+    estimate_genome_size, looks_like_rna, open_mapped_with_capacity, open_reader_with_capacity, spectrum_svg, Adapters,
+    CountWidth, GenomeEstimate, KmerCounter, MateCountError, NonAcgt, PairedReader, RecordError, Sample, SaturationFit,
+    SaturationTracker, SeqRecord, SeqStats, StrandCounts, DEFAULT_HISTORY_WINDOW, DEFAULT_MIN_COUNT, PHRED64_OFFSET,
+    PHRED_OFFSET,
+};
+use unique_kmers_evolution::sketch::{murmur3_64, MINHASH_SEED};
 
-
-/// Fast hash map
-use rustc_hash::FxHashMap;
+/// Order of the lines written by --dump-kmers/--dump-fasta with --sorted
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum DumpOrder {
+    /// By k-mer, in byte order
+    Kmer,
+    /// By decreasing count, ties by k-mer
+    Count,
+}
 
 /// Command-line arguments
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Length of k-mers
-    #[arg(short, long)]
+    #[arg(
+        short,
+        long,
+        value_parser = parse_k,
+        required_unless_present_any = ["k_range", "seed_pattern", "validate"],
+        conflicts_with_all = ["k_range", "seed_pattern"]
+    )]
+    k: Option<usize>,
+
+    /// Count several k-mer lengths in one pass over the reads, e.g. `17,21,25`
+    #[arg(
+        long,
+        value_name = "K,K,...",
+        value_delimiter = ',',
+        num_args = 1..,
+        value_parser = parse_k,
+        conflicts_with_all = ["histogram", "spectrum_svg", "dump_kmers", "dump_fasta", "top_n", "load", "save"]
+    )]
+    k_range: Vec<usize>,
+
+    /// Count only minimizers: of every W consecutive k-mers, the one with the smallest hash
+    #[arg(long, value_name = "W", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    minimizer: Option<usize>,
+
+    /// Count spaced seeds instead of k-mers: a mask such as `111010011` whose `1` positions are read
+    /// into the key and `0` positions ignored (the window length takes the place of --k)
+    #[arg(
+        long,
+        value_name = "MASK",
+        value_parser = parse_seed_pattern,
+        conflicts_with_all = ["k_range", "load", "save"]
+    )]
+    seed_pattern: Option<String>,
+
+    /// Input FASTA/FASTQ files, counted in order into one k-mer set (`-` reads standard input)
+    #[arg(short, long, num_args = 1.., required_unless_present = "input_1", conflicts_with = "input_1")]
+    input: Vec<PathBuf>,
+
+    /// R1 file of paired-end reads, read in lockstep with --input-2 and handled as an
+    /// interleaved input: both mates are counted into one k-mer set and kept or dropped together
+    #[arg(long, value_name = "R1", requires = "input_2", conflicts_with_all = ["interleaved", "jaccard", "matrix"])]
+    input_1: Option<PathBuf>,
+
+    /// R2 file, holding the mate of each --input-1 record in the same order; the two must hold
+    /// as many records
+    #[arg(long, value_name = "R2", requires = "input_1")]
+    input_2: Option<PathBuf>,
+
+    /// Read buffer size for the inputs, in bytes or with a K or M suffix (KiB, MiB); compressed
+    /// inputs get one buffer for the file and one for the decompressed stream
+    #[arg(long, value_name = "SIZE", default_value = "1M", value_parser = parse_size)]
+    buffer_size: usize,
+
+    /// Memory-map uncompressed input files and parse them in place, which saves the read calls
+    /// and buffer copies on fast storage; compressed files and standard input are read as usual
+    #[arg(long)]
+    mmap: bool,
+
+    /// Treat the input as interleaved pairs, read 1 and read 2 alternating: --take-every,
+    /// --subsample and --reservoir keep or drop whole pairs, samples fall between pairs and
+    /// pairs are reported alongside reads. Both mates are counted alike.
+    #[arg(long)]
+    interleaved: bool,
+
+    /// Skip k-mer windows containing a base with Phred quality below Q (FASTQ only; FASTA input is
+    /// counted as is)
+    #[arg(long, value_name = "Q")]
+    min_qual: Option<u8>,
+
+    /// Mask exact matches of the adapter sequences in this FASTA/FASTQ file out of every read
+    /// before counting, so no counted k-mer overlaps one
+    #[arg(long, value_name = "PATH")]
+    adapters: Option<PathBuf>,
+
+    /// Trim FASTQ read ends where the mean Phred quality over --trim-window bases falls below Q,
+    /// before counting (FASTA input is not trimmed)
+    #[arg(long, value_name = "Q")]
+    trim_qual: Option<u8>,
+
+    /// Sliding window length for --trim-qual
+    #[arg(long, value_name = "W", default_value_t = 4, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..), requires = "trim_qual")]
+    trim_window: usize,
+
+    /// Read FASTQ qualities as Phred+64 (Illumina 1.3 to 1.7) rather than Phred+33, for
+    /// --min-qual and --trim-qual
+    #[arg(long)]
+    phred64: bool,
+
+    /// Skip reads shorter than this many bases after trimming; defaults to --k
+    #[arg(long, value_name = "L")]
+    min_len: Option<usize>,
+
+    /// Skip reads whose sequence is an exact duplicate of one already counted, a coarse
+    /// PCR/optical duplicate filter. A 64-bit hash of every distinct read is kept, about 10 to
+    /// 20 bytes each (1-2 GB per 100 million distinct reads)
+    #[arg(long, conflicts_with_all = ["interleaved", "input_1"])]
+    dedup: bool,
+
+    /// Occurrences needed for a k-mer to count as solid
+    #[arg(long, default_value_t = DEFAULT_MIN_COUNT, value_parser = clap::value_parser!(u16).range(1..))]
+    min_count: u16,
+
+    /// Bits per count (8 or 16) in the table of every possible k-mer used for k up to 12. 8 bits
+    /// halve its memory but stop counts at 255. By default 8 unless --min-count is over 255 or full
+    /// counts are kept (--histogram and the like). k-mers in hash maps always take 16-bit counts,
+    /// which an 8-bit count would not shrink: each entry is padded to its key
+    #[arg(long, value_name = "BITS", value_parser = parse_count_width)]
+    count_width: Option<CountWidth>,
+
+    /// Keep k-mers containing bases other than A/C/G/T (e.g. N)
+    #[arg(long)]
+    allow_ambiguous: bool,
+
+    /// Count lowercase (soft-masked) bases separately from uppercase ones
+    #[arg(long)]
+    case_sensitive: bool,
+
+    /// Skip low-complexity k-mers such as `AAAAAAAA` or `ATATATAT`, whose dinucleotide entropy is
+    /// below --complexity-threshold
+    #[arg(long)]
+    mask_low_complexity: bool,
+
+    /// Dinucleotide entropy, in bits, under which --mask-low-complexity skips a k-mer window
+    /// (0 for homopolymers, 1 for dinucleotide repeats, ~1.6 for trinucleotide repeats, at most 4)
+    #[arg(long, value_name = "BITS", default_value_t = 2.0, requires = "mask_low_complexity")]
+    complexity_threshold: f64,
+
+    /// Skip k-mers holding a run of the same base longer than H, such as poly-A tails
+    #[arg(long, value_name = "H", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    max_homopolymer: Option<usize>,
+
+    /// Skip k-mers whose share of G and C bases is below this fraction, e.g. 0.3
+    #[arg(long, value_name = "FRACTION", value_parser = parse_gc_fraction, conflicts_with = "protein")]
+    min_gc: Option<f64>,
+
+    /// Skip k-mers whose share of G and C bases is above this fraction, e.g. 0.6
+    #[arg(long, value_name = "FRACTION", value_parser = parse_gc_fraction, conflicts_with = "protein")]
+    max_gc: Option<f64>,
+
+    /// Count k-mers on the read strand only, without merging reverse complements (strand-specific
+    /// data such as directional RNA-seq). Changes what the counts mean: a k-mer and its reverse
+    /// complement become two distinct k-mers, roughly doubling the distinct count
+    #[arg(long)]
+    forward_only: bool,
+
+    /// Treat input as RNA (U pairs with A); auto-detected from the first record otherwise
+    #[arg(long)]
+    rna: bool,
+
+    /// Treat input as protein: count k-peptides over the 20 standard amino acids, keyed as read
+    /// since proteins have no reverse complement (other residues, such as X or *, are skipped)
+    #[arg(long, conflicts_with_all = ["rna", "mask_low_complexity", "nthash"])]
+    protein: bool,
+
+    /// Worker threads for k-mer counting (defaults to the number of logical CPUs)
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Estimate distinct k-mers with a HyperLogLog sketch instead of storing them
+    #[arg(long, group = "sketch")]
+    estimate: bool,
+
+    /// HyperLogLog precision for --estimate (2^p registers, relative error 1.04/sqrt(2^p))
+    #[arg(long, default_value_t = 14, value_parser = clap::value_parser!(u8).range(4..=18))]
+    hll_precision: u8,
+
+    /// Bound memory to about this many MB by estimating solid k-mers with a Count-Min sketch
+    #[arg(long, value_name = "MB", conflicts_with = "estimate", group = "sketch")]
+    max_memory: Option<usize>,
+
+    /// Stop reading once the estimated memory of the exact k-mer sets exceeds this many MB, and
+    /// finish as after Ctrl-C: final statistics and dumps cover the reads counted so far
+    #[arg(
+        long,
+        value_name = "MB",
+        conflicts_with_all = ["sketch", "jaccard"],
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    max_memory_mb: Option<usize>,
+
+    /// Hash k-mers for --estimate/--max-memory with the rolling ntHash, without building them
+    #[arg(
+        long,
+        requires = "sketch",
+        conflicts_with_all = ["allow_ambiguous", "case_sensitive", "seed_pattern", "minimizer"]
+    )]
+    nthash: bool,
+
+    /// Count into one concurrent hash map that all --threads workers insert into, instead of
+    /// bucketing k-mers by shard and updating each shard from one thread (k above 12 only)
+    #[arg(long, conflicts_with_all = ["estimate", "max_memory"])]
+    concurrent_map: bool,
+
+    /// Write the k-mer abundance histogram (abundance, num_kmers) as TSV at the end
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["estimate", "max_memory"])]
+    histogram: Option<PathBuf>,
+
+    /// Draw the k-mer abundance histogram as an SVG bar chart at the end, marking the coverage
+    /// peak when --estimate-genome-size would find one
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["estimate", "max_memory"])]
+    spectrum_svg: Option<PathBuf>,
+
+    /// Estimate the genome size from the k-mer spectrum at the end: the k-mers past the error
+    /// valley divided by the depth of the main coverage peak (naive GenomeScope-style estimate)
+    #[arg(long, conflicts_with_all = ["estimate", "max_memory", "k_range", "jaccard", "matrix"])]
+    estimate_genome_size: bool,
+
+    /// Write every solid canonical k-mer, one per line, at the end
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["estimate", "max_memory"])]
+    dump_kmers: Option<PathBuf>,
+
+    /// Write every solid canonical k-mer as a FASTA record at the end
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["estimate", "max_memory"])]
+    dump_fasta: Option<PathBuf>,
+
+    /// Sort the --dump-kmers/--dump-fasta output instead of writing it in map order, so runs can
+    /// be diffed: by k-mer in byte order (the default) or by decreasing count (which keeps full counts)
+    #[arg(long, value_enum, value_name = "ORDER", num_args = 0..=1, default_missing_value = "kmer")]
+    sorted: Option<DumpOrder>,
+
+    /// Report how many distinct solid k-mers are reverse-complement palindromes (their own reverse
+    /// complement, like many restriction sites) at the end; only even k can have any
+    #[arg(long, conflicts_with_all = ["estimate", "max_memory", "protein"])]
+    count_palindromes: bool,
+
+    /// Tally whether each counted k-mer was read as its canonical form (forward) or as its
+    /// reverse complement, and report the balance at the end: a strong skew can point at
+    /// strand bias in library prep
+    #[arg(long, conflicts_with_all = ["forward_only", "protein", "nthash", "minimizer"])]
+    strand_stats: bool,
+
+    /// Print the N most frequent k-mers with their counts at the end, also in the --json summary
+    #[arg(long, value_name = "N", conflicts_with_all = ["estimate", "max_memory"], value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    top_n: Option<usize>,
+
+    /// Seed the count from a k-mer snapshot written by --save (must use the same --k)
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["estimate", "max_memory"])]
+    load: Option<PathBuf>,
+
+    /// Save the final k-mer map with counts so a later run can --load it
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["estimate", "max_memory"])]
+    save: Option<PathBuf>,
+
+    /// Write a bottom-k MinHash sketch of the counted k-mers as JSON at the end (MurmurHash3 of
+    /// the canonical k-mer seeded with --hash-seed (default 42), as Mash and sourmash hash them)
+    #[arg(long, value_name = "PATH", group = "minhash", conflicts_with_all = ["k_range", "load"])]
+    sketch_out: Option<PathBuf>,
+
+    /// Sketch every --input file on its own and write the matrix of their pairwise Jaccard
+    /// distances (1 - Jaccard index of the MinHash sketches) as TSV, with file names as labels
+    #[arg(
+        long,
+        value_name = "PATH",
+        group = "minhash",
+        conflicts_with_all = ["k_range", "histogram", "spectrum_svg", "dump_kmers", "dump_fasta", "top_n", "load", "save"]
+    )]
+    matrix: Option<PathBuf>,
+
+    /// Hashes kept in each MinHash sketch (--sketch-out, --matrix)
+    #[arg(long, value_name = "N", default_value_t = 1000, requires = "minhash", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    sketch_size: usize,
+
+    /// Seed of the k-mer hashes of every sketch (--estimate, --max-memory, --sketch-out, --matrix):
+    /// runs with the same seed and input give identical sketches. Exact counts do not depend on it
+    #[arg(long, value_name = "N", default_value_t = MINHASH_SEED)]
+    hash_seed: u32,
+
+    /// Compare the two --input files instead of pooling them: count each into its own k-mer set
+    /// and report the Jaccard index of their solid k-mers (both are read to the end, without auto-stop)
+    #[arg(long, conflicts_with_all = ["k_range", "sketch", "histogram", "spectrum_svg", "dump_kmers", "dump_fasta", "top_n", "load", "save", "minhash"])]
+    jaccard: bool,
+
+    /// Reads between sampling points, where stats are computed, logged and pushed; growth is per interval
+    #[arg(long, value_name = "N", default_value_t = 10000, value_parser = clap::value_parser!(u64).range(1..))]
+    report_every: u64,
+
+    /// Print the estimated memory of the k-mer storage and the process resident set size (where
+    /// the OS reports it) at each sampling interval, also in the --json and WebSocket output
+    #[arg(long)]
+    report_memory: bool,
+
+    /// Report the share of reads that add nothing new, every k-mer of them being solid already,
+    /// at each sampling interval: a read-level view of library complexity. Counting turns
+    /// sequential; with --min-qual each high-quality stretch of a read counts as a read.
+    #[arg(long, conflicts_with_all = ["sketch", "jaccard", "matrix"])]
+    redundancy_stats: bool,
+
+    /// Samples averaged into the growth and acceleration trends used by auto-stop
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_HISTORY_WINDOW, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    history_window: usize,
+
+    /// Process a random fraction of the reads, e.g. 0.1 for 10%, chosen reproducibly from --seed
+    #[arg(long, value_name = "FRACTION", value_parser = parse_fraction, group = "random")]
+    subsample: Option<f64>,
+
+    /// Process a uniform random sample of exactly N reads of the whole input (all of them if it has
+    /// fewer), chosen reproducibly from --seed. The sample is held in memory before counting.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        group = "random",
+        conflicts_with_all = ["take_every", "two_pass", "jaccard", "matrix"]
+    )]
+    reservoir: Option<usize>,
+
+    /// Process only every Nth read (the 1st, N+1th, ...), a deterministic alternative to --subsample
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), conflicts_with = "subsample")]
+    take_every: Option<u64>,
+
+    /// Random seed for --subsample and --reservoir
+    #[arg(long, default_value_t = 0, requires = "random")]
+    seed: u64,
+
+    /// Stop after this many reads, whether or not the curve has saturated
+    #[arg(long, value_name = "READS", value_parser = clap::value_parser!(u64).range(1..))]
+    max_reads: Option<u64>,
+
+    /// Stop once every k has this many distinct solid k-mers (distinct ones with --estimate),
+    /// whether or not the curve has saturated; reads are then counted one at a time, so the run
+    /// ends on the read that reaches N
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_distinct: Option<u64>,
+
+    /// Stop after this much wall-clock time, e.g. `90s`, `30m` or `2h`, and report and write the
+    /// results so far, as for Ctrl-C; the budget covers every pass of the run
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    max_time: Option<Duration>,
+
+    /// Reads that must be processed before auto-stop may trigger
+    #[arg(long, value_name = "READS", default_value_t = 50000)]
+    stop_min_reads: u64,
+
+    /// Stop early once the absolute average acceleration falls below this value
+    #[arg(long, value_name = "ACCEL", default_value_t = 20.0)]
+    stop_accel_threshold: f32,
+
+    /// Stop early instead once an interval adds fewer new k-mers than this percentage of
+    /// those counted so far (replaces the acceleration rule)
+    #[arg(long, value_name = "PCT", value_parser = parse_percentage, conflicts_with = "stop_accel_threshold")]
+    saturation_pct: Option<f64>,
+
+    /// Fit a saturating curve (Michaelis-Menten) to the k-mers tracked for auto-stop at the end
+    /// and project how many reads would reach this fraction of its asymptote, e.g. 0.95
+    #[arg(long, value_name = "FRACTION", value_parser = parse_target, conflicts_with_all = ["jaccard", "matrix"])]
+    target_saturation: Option<f64>,
+
+    /// Read the input twice: a quick HyperLogLog pass estimates its total distinct k-mers, then the
+    /// exact pass reports the distinct k-mers seen as a percentage of that total, and
+    /// --saturation-pct compares an interval's new distinct k-mers with the total instead
+    #[arg(long, conflicts_with_all = ["sketch", "jaccard", "matrix", "load"])]
+    two_pass: bool,
+
+    /// Never stop early; read the whole input
+    #[arg(long, conflicts_with_all = ["stop_accel_threshold", "saturation_pct"])]
+    no_auto_stop: bool,
+
+    /// Append k, reads, distinct, solid, growth and acceleration to this TSV at each sampling interval
+    #[arg(long, value_name = "PATH")]
+    progress_log: Option<PathBuf>,
+
+    /// Write the whole curve as JSON at the end: reads, distinct and solid k-mers, growth and
+    /// acceleration at every sampling interval, not only the window auto-stop averages
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["jaccard", "matrix"])]
+    curve_out: Option<PathBuf>,
+
+    /// Write the whole curve as a Parquet table at the end, with the --progress-log columns (k,
+    /// reads, distinct, solid, growth, accel) typed, for DataFrame tools
+    #[cfg(feature = "parquet")]
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["jaccard", "matrix"])]
+    parquet: Option<PathBuf>,
+
+    /// Skip records that fail to parse, and count them, instead of stopping at the first one;
+    /// with paired reads, a mate skipped for its qualities takes the other mate with it
+    #[arg(long)]
+    skip_errors: bool,
+
+    /// Only check that the inputs parse end to end: read every record and report the records,
+    /// bases and malformed records of each input, without counting k-mers or serving the
+    /// WebSocket. Exits with status 1 if any record fails to parse
+    #[arg(long)]
+    validate: bool,
+
+    /// Log filter for the diagnostic events written to stderr, such as `debug` or
+    /// `unique_kmers_evolution=trace,warp=info`; overrides RUST_LOG. Off unless either is set.
+    #[arg(long, value_name = "FILTER", value_parser = parse_log_filter)]
+    log_level: Option<String>,
+
+    /// Address the WebSocket server binds to
+    #[arg(long, default_value = "127.0.0.1")]
+    ws_host: IpAddr,
+
+    /// Port the WebSocket server listens on
+    #[arg(long, default_value_t = 3030)]
+    ws_port: u16,
+
+    /// PEM certificate chain to serve the dashboard, `/ws` and `/stats` over TLS (https and
+    /// wss://), with --tls-key
+    #[arg(long, value_name = "PEM", requires = "tls_key", conflicts_with = "no_ws")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key of --tls-cert
+    #[arg(long, value_name = "PEM", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Require this secret from clients of `/ws` and `/stats`, as a `token` query parameter or
+    /// an `Authorization: Bearer` header; others get 401 Unauthorized
+    #[arg(long, value_name = "SECRET", conflicts_with = "no_ws")]
+    ws_token: Option<String>,
+
+    /// Send WebSocket clients at most this many updates per second, holding back the samples
+    /// that come faster; the latest one is always sent, at the latest once the reads end
+    #[arg(long, value_name = "HZ", value_parser = parse_rate, conflicts_with = "no_ws")]
+    ws_max_rate: Option<f64>,
+
+    /// Do not start the web server (dashboard, `/ws` and `/stats`), e.g. for batch runs
+    #[arg(long)]
+    no_ws: bool,
+
+    /// Report the base composition (A/C/G/T/N counts, GC%) and read lengths (mean, median, N50) of
+    /// the processed reads, before trimming, at the end
+    #[arg(long)]
+    seq_stats: bool,
+
+    /// Print one JSON object per sampling interval and a final summary instead of text
+    #[arg(long)]
+    json: bool,
+
+    /// Print the large counts of the text output, from progress lines to the end-of-run
+    /// summary, with thousands separators, e.g. 1,234,567; --json output keeps plain numbers
+    #[arg(long)]
+    human: bool,
+
+    /// Decimal places of the average growth and acceleration on the progress lines
+    #[arg(long, value_name = "DIGITS", default_value_t = 1, value_parser = clap::value_parser!(u8).range(..=9))]
+    precision: u8,
+}
+
+impl Args {
+    /// The k-mer lengths to count, in increasing order
+    fn ks(&self) -> Vec<usize> {
+        let mut ks = match (self.k, &self.seed_pattern) {
+            (Some(k), _) => vec![k],
+            (None, Some(pattern)) => vec![pattern.len()],
+            (None, None) => self.k_range.clone(),
+        };
+        ks.sort_unstable();
+        ks.dedup();
+        ks
+    }
+
+    /// Reads shorter than this, after trimming, are not counted (--min-len, or the smallest k)
+    fn min_len(&self) -> usize {
+        self.min_len.unwrap_or(self.ks()[0])
+    }
+
+    /// Whether the reads come in pairs, interleaved or from --input-1/--input-2
+    fn paired(&self) -> bool {
+        self.interleaved || self.input_2.is_some()
+    }
+
+    /// A count as the text output prints it, with thousands separators under --human
+    fn count(&self, n: u64) -> String {
+        if self.human { thousands(n) } else { n.to_string() }
+    }
+
+    /// [`count`](Self::count) of a change between samples, which may be negative
+    fn change(&self, n: i64) -> String {
+        let sign = if n < 0 { "-" } else { "" };
+        format!("{}{}", sign, self.count(n.unsigned_abs()))
+    }
+
+    /// GC fractions of the k-mers counted, when --min-gc or --max-gc restricts them
+    fn gc_range(&self) -> Option<(f64, f64)> {
+        (self.min_gc.is_some() || self.max_gc.is_some()).then(|| (self.min_gc.unwrap_or(0.0), self.max_gc.unwrap_or(1.0)))
+    }
+
+    /// Every file read, R2 included
+    fn input_files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.input.iter().chain(&self.input_2)
+    }
+
+    /// Whether --min-qual or --trim-qual read the FASTQ qualities
+    fn uses_quality(&self) -> bool {
+        self.min_qual.is_some() || self.trim_qual.is_some()
+    }
+
+    /// Whether every sample of the curves must be kept until the end
+    fn keeps_curve(&self) -> bool {
+        #[cfg(feature = "parquet")]
+        let parquet = self.parquet.is_some();
+        #[cfg(not(feature = "parquet"))]
+        let parquet = false;
+        self.target_saturation.is_some() || self.curve_out.is_some() || parquet
+    }
+}
+
+/// Statistics at one sampling interval, as printed with --json (once per k with --k-range)
+#[derive(Serialize)]
+struct Progress {
+    /// Input file being processed
+    file: String,
     k: usize,
+    reads: u64,
+    distinct_kmers: u64,
+    solid_kmers: u64,
+    /// k-mer positions counted, repeats included
+    total_kmers: u64,
+    avg_growth: f32,
+    avg_accel: f32,
+    /// Since the previous interval in progress lines, over the whole run in the summary
+    #[serde(flatten)]
+    throughput: Throughput,
+    /// --report-memory: estimated bytes of the k-mer storage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    map_bytes: Option<u64>,
+    /// --report-memory: bits per stored count, unless a sketch keeps no counts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count_bits: Option<u32>,
+    /// --report-memory: resident set size of the whole process
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rss_bytes: Option<u64>,
+    /// --two-pass: distinct k-mers of the whole input, estimated by the first pass
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estimated_total: Option<u64>,
+    /// --two-pass: distinct k-mers seen, as a percentage of `estimated_total`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    saturation_pct: Option<f64>,
+    /// --redundancy-stats: percentage of reads whose k-mers were all solid already, since
+    /// the previous interval in progress lines, over the whole run in the summary
+    #[serde(skip_serializing_if = "Option::is_none")]
+    redundant_pct: Option<f64>,
+}
+
+/// Processing speed over some span of time
+#[derive(Clone, Copy, Serialize)]
+struct Throughput {
+    reads_per_sec: f64,
+    bases_per_sec: f64,
+}
 
-    /// Input FASTA file
-    #[arg(short, long)]
-    input: PathBuf,
+impl Throughput {
+    fn new(reads: u64, bases: u64, elapsed: Duration) -> Self {
+        let secs = elapsed.as_secs_f64();
+        if secs > 0.0 {
+            Throughput { reads_per_sec: reads as f64 / secs, bases_per_sec: bases as f64 / secs }
+        } else {
+            Throughput { reads_per_sec: 0.0, bases_per_sec: 0.0 }
+        }
+    }
 }
 
-enum RecordReader<R: Read> {
-    Fasta(fasta::Records<BufReader<R>>),
-    Fastq(fastq::Records<BufReader<R>>),
+impl fmt::Display for Throughput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.0} reads/s, {:.2} Mbp/s", self.reads_per_sec, self.bases_per_sec / 1e6)
+    }
 }
 
-impl<R: Read> RecordReader<R> {
-    fn next_record(&mut self) -> Option<Result<Vec<u8>, Box<dyn std::error::Error>>> {
-        match self {
-            RecordReader::Fasta(reader) => reader.next().map(|r| {
-                r.map(|rec| rec.seq().to_vec())
-                    .map_err(|e| e.into())
-            }),
-            RecordReader::Fastq(reader) => reader.next().map(|r| {
-                r.map(|rec| rec.seq().to_vec())
-                    .map_err(|e| e.into())
-            }),
-        }
-    }
-}
-
-/// Fast reverse complement for &[u8]
-fn reverse_complement(kmer: &[u8]) -> Vec<u8> {
-    kmer.iter()
-        .rev()
-        .map(|&c| match c {
-            b'A' => b'T',
-            b'T' => b'A',
-            b'C' => b'G',
-            b'G' => b'C',
-            _ => c,
-        })
-        .collect()
+/// One sample of a k's curve, as written by --curve-out
+#[derive(Clone, Copy, Serialize)]
+struct CurvePoint {
+    reads: u64,
+    distinct_kmers: u64,
+    solid_kmers: u64,
+    /// K-mers auto-stop follows: solid ones, or distinct ones with --estimate
+    #[serde(skip)]
+    kmers: u64,
+    /// Of the followed k-mers, since the previous sample
+    growth: i64,
+    acceleration: i64,
 }
 
-/// Return the canonical k-mer (lexicographically smallest between kmer and its reverse complement)
-fn canonical_kmer(kmer: &[u8]) -> Vec<u8> {
-    let rc = reverse_complement(kmer);
-    if kmer <= rc.as_slice() {
-        kmer.to_vec()
-    } else {
-        rc
+/// Every sample of one k's curve, as written by --curve-out
+#[derive(Serialize)]
+struct CurveFile<'a> {
+    k: usize,
+    samples: &'a [CurvePoint],
+}
+
+/// MinHash sketch written by --sketch-out, with sourmash's names for the signature fields
+#[derive(Serialize)]
+struct SketchFile<'a> {
+    /// First 64 bits of MurmurHash3_x64_128 of each k-mer's uppercase key bytes
+    hash_function: &'static str,
+    seed: u32,
+    ksize: usize,
+    /// Hashes kept (fewer if the input had fewer distinct k-mers)
+    num: usize,
+    molecule: &'static str,
+    /// Whether k-mers were hashed in canonical form (false with --forward-only)
+    canonical: bool,
+    /// Mask of the positions hashed, with --seed-pattern
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed_pattern: Option<&'a str>,
+    /// Window of --minimizer: only minimizers were hashed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minimizer: Option<usize>,
+    filenames: Vec<String>,
+    /// The smallest hashes, in increasing order
+    mins: Vec<u64>,
+}
+
+/// Result of --jaccard, as printed with --json
+#[derive(Serialize)]
+struct Comparison {
+    k: usize,
+    a: String,
+    b: String,
+    solid_a: u64,
+    solid_b: u64,
+    /// Solid k-mers of both inputs
+    intersection: u64,
+    /// Solid k-mers of either input
+    union: u64,
+    jaccard: f64,
+    /// Reading was cut short by Ctrl-C or --max-time, so the second set may be incomplete
+    interrupted: bool,
+}
+
+/// End-of-run statistics, as printed with --json
+#[derive(Serialize)]
+struct Summary {
+    #[serde(flatten)]
+    progress: Progress,
+    stopped_early: bool,
+    /// Reading was cut short by Ctrl-C
+    interrupted: bool,
+    /// Reading was cut short by --max-memory-mb
+    memory_capped: bool,
+    /// Reading was cut short by --max-time
+    time_limited: bool,
+    /// Why reading stopped, as the final WebSocket event names it
+    stop_reason: &'static str,
+    skipped_kmers: u64,
+    /// Windows skipped by --mask-low-complexity
+    #[serde(skip_serializing_if = "Option::is_none")]
+    low_complexity_kmers: Option<u64>,
+    /// --count-palindromes: distinct solid k-mers equal to their reverse complement
+    #[serde(skip_serializing_if = "Option::is_none")]
+    palindromic_kmers: Option<u64>,
+    /// Windows skipped by --max-homopolymer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    homopolymer_kmers: Option<u64>,
+    /// Windows skipped by --min-gc and --max-gc
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gc_kmers: Option<u64>,
+    /// --strand-stats: counted k-mers by the strand they were read from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    strands: Option<StrandReport>,
+    /// --interleaved: read pairs processed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pairs: Option<u64>,
+    /// Reads shorter than --min-len (after trimming), which were not counted
+    short_reads: u64,
+    /// Bases masked as --adapters matches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    adapter_bases: Option<u64>,
+    /// --dedup: reads skipped as exact duplicates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duplicate_reads: Option<u64>,
+    /// Reads holding a byte other than A/C/G/T/U, unless counting peptides
+    #[serde(skip_serializing_if = "Option::is_none")]
+    non_acgt_reads: Option<u64>,
+    /// Those bytes with their occurrences, most frequent first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    non_acgt_bases: Option<Vec<ByteCount>>,
+    /// Records that failed to parse and were skipped with --skip-errors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    malformed_records: Option<u64>,
+    /// Relative error of `distinct_kmers` when it is estimated
+    distinct_error: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seq_stats: Option<SeqStatsReport>,
+    /// What each input added to this k, with several inputs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    per_file: Option<Vec<FileReport>>,
+    /// --estimate-genome-size: null when the spectrum has no clear coverage peak
+    #[serde(skip_serializing_if = "Option::is_none")]
+    genome_estimate: Option<Option<GenomeReport>>,
+    /// --top-n: the most frequent k-mers, highest first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_kmers: Option<Vec<TopKmer>>,
+    /// --target-saturation: null when no saturating curve fits the samples
+    #[serde(skip_serializing_if = "Option::is_none")]
+    projection: Option<Option<Projection>>,
+}
+
+/// --target-saturation, as printed with --json
+#[derive(Clone, Copy, Serialize)]
+struct Projection {
+    /// K-mers the fitted curve approaches
+    asymptote: f64,
+    /// Reads after which the curve has added half of its new k-mers
+    half_saturation_reads: f64,
+    /// Share of the asymptote the model puts at the reads processed
+    saturation: f64,
+    target: f64,
+    /// Reads needed to reach the target share
+    target_reads: u64,
+    /// Of those, reads beyond the ones processed; 0 once the target is reached
+    more_reads: u64,
+}
+
+/// A byte of the reads other than A/C/G/T/U, as printed with --json
+#[derive(Serialize)]
+struct ByteCount {
+    /// See `byte_label`
+    byte: String,
+    count: u64,
+}
+
+/// A read byte as printed: itself if visible, otherwise its name or hex code
+fn byte_label(byte: u8) -> String {
+    match byte {
+        b' ' => "space".to_string(),
+        b'\t' => "tab".to_string(),
+        b'\r' => "CR".to_string(),
+        _ if byte.is_ascii_graphic() => (byte as char).to_string(),
+        _ => format!("0x{:02X}", byte),
     }
 }
 
-/// WebSocket handling
-async fn handle_connection(ws: WebSocket, rx: Arc<Mutex<mpsc::Receiver<(u32, u32)>>>) {
-    let (mut ws_tx, _) = ws.split();
-    let mut rx = rx.lock().await;
-    while let Some((reads, kmers)) = rx.recv().await {
-        let message = format!("{} {}", reads, kmers);
-        if ws_tx.send(Message::text(message)).await.is_err() {
-            break;
+/// One --top-n k-mer, as printed with --json
+#[derive(Serialize)]
+struct TopKmer {
+    kmer: String,
+    count: u16,
+}
+
+/// --strand-stats, as printed with --json
+#[derive(Serialize)]
+struct StrandReport {
+    forward: u64,
+    reverse: u64,
+    palindromic: u64,
+    /// Share of `forward` in `forward + reverse`
+    forward_fraction: f64,
+}
+
+impl From<StrandCounts> for StrandReport {
+    fn from(strands: StrandCounts) -> Self {
+        StrandReport {
+            forward: strands.forward,
+            reverse: strands.reverse,
+            palindromic: strands.palindromic,
+            forward_fraction: strands.forward_fraction(),
         }
     }
 }
 
+/// One input's contribution to one k, as printed with --json
+#[derive(Serialize)]
+struct FileReport {
+    file: String,
+    reads: u64,
+    bases: u64,
+    new_distinct_kmers: u64,
+    new_solid_kmers: u64,
+}
 
-fn open_reader(path: &PathBuf) -> Result<RecordReader<impl Read>, Box<dyn std::error::Error>> {
-    let file = File::open(path)?;
-    let reader: Box<dyn Read> = if path.extension().map(|e| e == "gz").unwrap_or(false) {
-        Box::new(MultiGzDecoder::new(file))
-    } else {
-        Box::new(file)
-    };
+/// --estimate-genome-size, as printed with --json
+#[derive(Clone, Copy, Serialize)]
+struct GenomeReport {
+    /// Abundance below which k-mers are taken as errors
+    error_cutoff: u16,
+    /// Abundance of the main peak, the k-mer coverage depth
+    peak_coverage: u16,
+    /// Per-base depth implied by the k-mer depth and the mean read length L, `peak * L / (L - k + 1)`
+    base_coverage: Option<f64>,
+    /// Occurrences of the k-mers past the error cutoff
+    kmers: u64,
+    genome_size: u64,
+}
+
+impl GenomeReport {
+    fn new(estimate: &GenomeEstimate, k: usize, mean_read_length: f64) -> Self {
+        let windows = mean_read_length - k as f64 + 1.0;
+        GenomeReport {
+            error_cutoff: estimate.error_cutoff,
+            peak_coverage: estimate.peak,
+            base_coverage: (windows > 0.0).then(|| estimate.peak as f64 * mean_read_length / windows),
+            kmers: estimate.kmers,
+            genome_size: estimate.genome_size,
+        }
+    }
+}
+
+/// --seq-stats, as printed with --json
+#[derive(Serialize)]
+struct SeqStatsReport {
+    reads: u64,
+    bases: u64,
+    a: u64,
+    c: u64,
+    g: u64,
+    /// T, or U in RNA
+    t: u64,
+    n: u64,
+    /// Other IUPAC codes and unexpected bytes
+    other: u64,
+    gc_percent: f64,
+    mean_length: f64,
+    median_length: usize,
+    n50: usize,
+}
 
-    let mut buffered = BufReader::new(reader);
+impl From<&SeqStats> for SeqStatsReport {
+    fn from(stats: &SeqStats) -> Self {
+        SeqStatsReport {
+            reads: stats.reads(),
+            bases: stats.bases(),
+            a: stats.count(b'A'),
+            c: stats.count(b'C'),
+            g: stats.count(b'G'),
+            t: stats.count(b'T') + stats.count(b'U'),
+            n: stats.count(b'N'),
+            other: stats.other(),
+            gc_percent: stats.gc_fraction() * 100.0,
+            mean_length: stats.mean_length(),
+            median_length: stats.median_length(),
+            n50: stats.n50(),
+        }
+    }
+}
 
-    // Peek at the first byte
-    let first_byte = {
-        let buf = buffered.fill_buf()?;
-        if buf.is_empty() {
-            return Err("Input file is empty".into());
+/// Human-readable message: stdout normally, stderr under --json so stdout stays parseable
+macro_rules! note {
+    ($args:expr, $($fmt:tt)*) => {
+        if $args.json {
+            eprintln!($($fmt)*);
+        } else {
+            println!($($fmt)*);
         }
-        buf[0]
     };
+}
 
-    // Decide format by first byte
-    if first_byte == b'>' {
-        Ok(RecordReader::Fasta(fasta::Reader::new(buffered).records()))
-    } else if first_byte == b'@' {
-        Ok(RecordReader::Fastq(fastq::Reader::new(buffered).records()))
-    } else {
-        Err(format!("Unknown file format: expected '>' or '@', got '{}'", first_byte as char).into())
+/// Print the --seq-stats block
+fn print_seq_stats(args: &Args, stats: &SeqStats) {
+    let report = SeqStatsReport::from(stats);
+    note!(
+        args,
+        "Bases: {} (A {}, C {}, G {}, T/U {}, N {}, other {}), GC {:.2}%",
+        args.count(report.bases), args.count(report.a), args.count(report.c), args.count(report.g),
+        args.count(report.t), args.count(report.n), args.count(report.other), report.gc_percent
+    );
+    note!(
+        args,
+        "Read length: mean {:.1}, median {}, N50 {}",
+        report.mean_length, report.median_length, report.n50
+    );
+}
+
+/// How many reads held bytes other than A/C/G/T/U, with a table of those bytes,
+/// warning about the ones that are not IUPAC codes either
+fn print_non_acgt(args: &Args, tally: &NonAcgt) {
+    println!(
+        "{} of {} reads ({:.2}%) hold bytes other than A/C/G/T/U:",
+        args.count(tally.reads()),
+        args.count(tally.checked()),
+        percent(tally.reads(), tally.checked())
+    );
+    for (byte, count) in tally.counts() {
+        println!("  {:<6} {}", byte_label(byte), args.count(count));
+    }
+    let unexpected = tally.unexpected();
+    if !unexpected.is_empty() {
+        let labels: Vec<String> = unexpected.into_iter().map(byte_label).collect();
+        println!(
+            "Warning: some of these bytes are not IUPAC nucleotide codes either ({}); the input may be malformed.",
+            labels.join(", ")
+        );
+    }
+}
+
+/// Table of what each input file added, with a pair of k-mer columns per k
+fn print_per_file(args: &Args, tracks: &[Track], files: &[FileContribution]) {
+    let names: Vec<String> = files.iter().map(|file| file.file.display().to_string()).collect();
+    let width = names.iter().map(String::len).chain([4]).max().unwrap_or(4);
+    let mut header = format!("{:<width$} {:>12} {:>14}", "File", "Reads", "Bases");
+    for track in tracks {
+        let suffix = if tracks.len() > 1 { format!(" k={}", track.counter.k()) } else { String::new() };
+        header += &format!(" {:>16} {:>16}", format!("+distinct{}", suffix), format!("+solid{}", suffix));
+    }
+    println!("{}", header);
+    for (name, file) in names.iter().zip(files) {
+        let mut row = format!("{:<width$} {:>12} {:>14}", name, args.count(file.reads), args.count(file.bases));
+        for (&distinct, &solid) in file.new_distinct.iter().zip(&file.new_solid) {
+            row += &format!(" {:>16} {:>16}", args.count(distinct), args.count(solid));
+        }
+        println!("{}", row);
     }
 }
 
+/// Number of records hashed together as one parallel batch
+const BATCH_SIZE: usize = 1000;
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
-    let k = args.k;
-
-    let mut unique_kmers: FxHashMap<Vec<u8>, bool> = FxHashMap::default();
-    let mut unique_solid_kmers = 0;
-
-    let (tx, rx) = mpsc::channel(100);
-    let rx = Arc::new(Mutex::new(rx));
-
-    // WebSocket route
-    let ws_route = warp::path("ws")
-        .and(warp::ws())
-        .map(move |ws: warp::ws::Ws| {
-            let rx = rx.clone();
-            ws.on_upgrade(move |socket| handle_connection(socket, rx))
-        });
+/// Batches of records the reader thread may get ahead of the counting by
+const READ_AHEAD_BATCHES: usize = 4;
 
-    tokio::spawn(async move {
-        warp::serve(ws_route)
-            .run(([127, 0, 0, 1], 3030))
-            .await;
-    });
+/// Live statistics at one sampling point, sent to WebSocket clients and served by `/stats` as JSON
+#[derive(Clone, Default, Serialize)]
+struct Snapshot {
+    k: usize,
+    reads: u64,
+    distinct: u64,
+    solid: u64,
+    /// New k-mers since the previous sample
+    growth: i64,
+    /// Change in growth since the previous sample
+    accel: i64,
+    /// Reads per second since the previous sample
+    rate: f64,
+    /// Estimated bytes of the k-mer storage, with --report-memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    map_bytes: Option<u64>,
+    /// Bits per stored count, with --report-memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count_bits: Option<u32>,
+    /// Resident set size of the process, with --report-memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rss_bytes: Option<u64>,
+    /// Distinct k-mers seen as a percentage of the input's estimated total, with --two-pass
+    #[serde(skip_serializing_if = "Option::is_none")]
+    saturation_pct: Option<f64>,
+    /// Percentage of the reads since the previous sample that added nothing, with --redundancy-stats
+    #[serde(skip_serializing_if = "Option::is_none")]
+    redundant_pct: Option<f64>,
+}
+
+/// Message to WebSocket clients, told apart by its `event` field
+#[derive(Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Update {
+    /// A sample of one k
+    Progress(Snapshot),
+    /// Counting has ended: the last message before the server closes the socket
+    Finished(Finished),
+}
 
-    let mut reader = open_reader(&args.input)?;
-    let mut idx = 0;
+/// Why and where counting ended, as sent to WebSocket clients
+#[derive(Clone, Serialize)]
+struct Finished {
+    /// `saturated`, `end_of_input`, `max_reads`, `max_distinct`, `memory_capped`, `time_limit` or `interrupted`
+    reason: &'static str,
+    reads: u64,
+    /// The final sample of each k
+    samples: Vec<Snapshot>,
+}
 
-    let mut prev_kmers = 0u32;
-    let mut growth_history: Vec<i32> = Vec::new();
-    let mut accel_history: Vec<i32> = Vec::new();
+/// Time the last clients are given to receive the final event and close their sockets
+const WS_CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
 
-    while let Some(seq_result) = reader.next_record() {
-        let sequence = seq_result?;
+/// Link from the counting loop to the web server
+struct Live {
+    /// Every update, for WebSocket clients
+    updates: broadcast::Sender<Update>,
+    /// The most recent snapshot of each k, for `GET /stats`
+    latest: Arc<std::sync::Mutex<Vec<Snapshot>>>,
+    /// --ws-max-rate: least time between two updates
+    min_interval: Option<Duration>,
+    /// When the last update went out
+    last_sent: Option<Instant>,
+    /// The latest snapshots held back by --ws-max-rate
+    held: Vec<Snapshot>,
+}
 
-        if sequence.len() < k {
-            idx += 1;
-            continue;
+impl Live {
+    fn new(
+        updates: broadcast::Sender<Update>,
+        latest: Arc<std::sync::Mutex<Vec<Snapshot>>>,
+        max_rate: Option<f64>,
+    ) -> Self {
+        let min_interval = max_rate.map(|hz| Duration::from_secs_f64(1.0 / hz));
+        Live { updates, latest, min_interval, last_sent: None, held: Vec::new() }
+    }
+
+    /// Serve the snapshots of a new sample, and send them to the WebSocket clients unless
+    /// the previous update went out too recently, in which case they wait for the next one
+    fn publish(&mut self, snapshots: Vec<Snapshot>) {
+        *self.latest.lock().unwrap() = snapshots.clone();
+        let now = Instant::now();
+        let too_soon = self.last_sent.zip(self.min_interval).is_some_and(|(last, min)| now - last < min);
+        if too_soon {
+            self.held = snapshots;
+        } else {
+            self.send(snapshots, now);
         }
+    }
 
-        for i in 0..=(sequence.len() - k) {
-            let kmer = &sequence[i..i + k];
-            let canonical = canonical_kmer(kmer);
-            match unique_kmers.get_mut(&canonical) {
-                Some(seen) => {
-                    if !*seen {
-                        *seen = true;
-                        unique_solid_kmers += 1;
-                    }
-                }
-                None => {
-                    unique_kmers.insert(canonical, false);
+    /// Send the snapshots held back, so clients end on the last sample
+    fn flush(&mut self) {
+        if !self.held.is_empty() {
+            let held = std::mem::take(&mut self.held);
+            self.send(held, Instant::now());
+        }
+    }
+
+    fn send(&mut self, snapshots: Vec<Snapshot>, now: Instant) {
+        for snapshot in snapshots {
+            // Sending only fails when no client is connected
+            let _ = self.updates.send(Update::Progress(snapshot));
+        }
+        self.last_sent = Some(now);
+        self.held.clear();
+    }
+
+    /// Send the final event with the latest samples, then give the clients a moment to
+    /// receive it and close before the process exits
+    async fn finish(&mut self, reason: &'static str, reads: u64) {
+        self.flush();
+        let samples = self.latest.lock().unwrap().clone();
+        let _ = self.updates.send(Update::Finished(Finished { reason, reads, samples }));
+        let deadline = Instant::now() + WS_CLOSE_TIMEOUT;
+        while self.updates.receiver_count() > 0 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+/// Rejection of a `/ws` or `/stats` request without the --ws-token secret
+#[derive(Debug)]
+struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+/// Requests carrying `token`, if one is set, in the query or an `Authorization: Bearer` header
+fn authorized(token: Option<Arc<str>>) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::query::<std::collections::HashMap<String, String>>()
+        .and(warp::header::optional::<String>("authorization"))
+        .and_then(move |query: std::collections::HashMap<String, String>, header: Option<String>| {
+            let token = token.clone();
+            async move {
+                let Some(token) = token else {
+                    return Ok(());
+                };
+                let bearer = header.as_deref().and_then(|header| header.strip_prefix("Bearer "));
+                let offered = [query.get("token").map(String::as_str), bearer];
+                if offered.into_iter().flatten().any(|offered| same_secret(offered, &token)) {
+                    Ok(())
+                } else {
+                    Err(warp::reject::custom(Unauthorized))
                 }
             }
+        })
+        .untuple_one()
+}
+
+/// Compare in time independent of where the strings differ, not to leak the secret
+fn same_secret(offered: &str, secret: &str) -> bool {
+    offered.len() == secret.len() && offered.bytes().zip(secret.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Turn an [`Unauthorized`] rejection into a 401 response
+async fn reject_unauthorized(rejection: warp::Rejection) -> Result<impl warp::Reply, warp::Rejection> {
+    match rejection.find::<Unauthorized>() {
+        Some(_) => Ok(warp::reply::with_status("missing or wrong --ws-token\n", warp::http::StatusCode::UNAUTHORIZED)),
+        None => Err(rejection),
+    }
+}
+
+/// One Prometheus metric served at `/metrics`
+struct Metric {
+    name: &'static str,
+    /// `counter` or `gauge`
+    kind: &'static str,
+    help: &'static str,
+    value: fn(&Snapshot) -> f64,
+}
+
+const METRICS: [Metric; 4] = [
+    Metric { name: "unique_kmers_reads_processed", kind: "counter", help: "Reads processed so far", value: |s| s.reads as f64 },
+    Metric { name: "unique_kmers_distinct_kmers", kind: "gauge", help: "Distinct k-mers seen so far", value: |s| s.distinct as f64 },
+    Metric { name: "unique_kmers_solid_kmers", kind: "gauge", help: "K-mers seen at least --min-count times", value: |s| s.solid as f64 },
+    Metric {
+        name: "unique_kmers_reads_per_second",
+        kind: "gauge",
+        help: "Reads processed per second over the last interval",
+        value: |s| s.rate,
+    },
+];
+
+/// The latest snapshots in the Prometheus text exposition format, one series per k;
+/// only the descriptions before the first sample
+fn prometheus_metrics(snapshots: &[Snapshot]) -> String {
+    let mut text = String::new();
+    for Metric { name, kind, help, value } in METRICS {
+        text.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+        for snapshot in snapshots {
+            text.push_str(&format!("{}{{k=\"{}\"}} {}\n", name, snapshot.k, value(snapshot)));
         }
+    }
+    text
+}
 
-        if idx % 10000 == 0 {
+/// Live chart page served at `/`
+const DASHBOARD: &str = include_str!("../plot.html");
 
-            let reads = idx as u32;
-            let kmers = unique_solid_kmers;
-            let growth = kmers as i32 - prev_kmers as i32;
+/// Updates buffered per WebSocket client; a client further behind skips the oldest ones
+const WS_BUFFER: usize = 100;
 
-            growth_history.push(growth);
-            if growth_history.len() > 10 {
-                growth_history.remove(0);
-            }
+/// Interval between keepalive pings, short enough for proxies with tight idle timeouts
+const WS_PING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How often a paused run checks whether it was resumed or interrupted
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Run state shared by the counting loops with Ctrl-C and the WebSocket commands
+struct Control {
+    /// Stop reading and report the results so far
+    interrupted: AtomicBool,
+    /// Hold the counting loop until resumed
+    paused: AtomicBool,
+    /// --max-time: when reading has to stop
+    deadline: Option<Instant>,
+}
+
+impl Control {
+    fn new(max_time: Option<Duration>) -> Self {
+        Control {
+            interrupted: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            deadline: max_time.map(|max_time| Instant::now() + max_time),
+        }
+    }
+
+    fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+    }
+
+    fn interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::Relaxed)
+    }
+
+    /// Whether the --max-time budget is used up
+    fn timed_out(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    fn paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Block the calling thread while paused, unless interrupted or out of time meanwhile
+    fn wait_while_paused(&self) {
+        while self.paused() && !self.interrupted() && !self.timed_out() {
+            std::thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+    }
+
+    /// Apply a text command sent by a WebSocket client: `pause`, `resume` or `abort`
+    fn command(&self, command: &str) -> Result<(), String> {
+        match command.trim() {
+            "pause" => self.set_paused(true),
+            "resume" => self.set_paused(false),
+            "abort" => self.interrupt(),
+            other => return Err(format!("unknown command {:?}, expected pause, resume or abort", other)),
+        }
+        Ok(())
+    }
+}
 
-            // Compute acceleration only if we have at least 2 growth values
-            if growth_history.len() >= 2 {
-                let acceleration = growth_history[growth_history.len() - 1]
-                    - growth_history[growth_history.len() - 2];
-                accel_history.push(acceleration);
-                if accel_history.len() > 10 {
-                    accel_history.remove(0);
+/// WebSocket handling: stream every update from the time the client connects,
+/// pinging between updates and dropping clients that stop answering
+async fn handle_connection(ws: WebSocket, mut rx: broadcast::Receiver<Update>, control: Arc<Control>) {
+    info!("WebSocket client connected");
+    let (mut ws_tx, mut ws_rx) = ws.split();
+    let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + WS_PING_INTERVAL, WS_PING_INTERVAL);
+    let mut awaiting_pong = false;
+    let mut finished = false;
+    let reason = loop {
+        if finished {
+            break "counting finished";
+        }
+        let message = tokio::select! {
+            update = rx.recv() => match update {
+                Ok(update) => {
+                    finished = matches!(update, Update::Finished(_));
+                    Message::text(serde_json::to_string(&update).expect("update serializes"))
                 }
-            }
+                // A slow client just misses the updates it fell behind on
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(missed, "WebSocket client fell behind, skipping updates");
+                    continue;
+                }
+                Err(RecvError::Closed) => break "counting finished",
+            },
+            _ = ping.tick() => {
+                // No answer to the previous ping within a whole interval
+                if awaiting_pong {
+                    break "no pong within the ping interval";
+                }
+                awaiting_pong = true;
+                Message::ping(Vec::new())
+            },
+            incoming = ws_rx.next() => match incoming {
+                Some(Ok(message)) if message.is_close() => break "closed by the client",
+                // Pongs, and anything else the client sends, show it is alive
+                Some(Ok(message)) => {
+                    awaiting_pong = false;
+                    if let Ok(command) = message.to_str() {
+                        match control.command(command) {
+                            Ok(()) => info!(command = command.trim(), "WebSocket command"),
+                            Err(e) => warn!(error = %e, "ignoring WebSocket message"),
+                        }
+                    }
+                    continue;
+                }
+                Some(Err(e)) => {
+                    debug!(error = %e, "WebSocket receive failed");
+                    break "receive failed";
+                }
+                None => break "connection dropped",
+            },
+        };
+        if let Err(e) = ws_tx.send(message).await {
+            debug!(error = %e, "WebSocket send failed");
+            break "send failed";
+        }
+    };
+    info!(reason, "WebSocket client disconnected");
+    let _ = ws_tx.close().await;
+}
 
-            // Compute averages
-            let avg_growth = growth_history.iter().sum::<i32>() as f32 / growth_history.len() as f32;
-            let avg_accel = if !accel_history.is_empty() {
-                accel_history.iter().sum::<i32>() as f32 / accel_history.len() as f32
-            } else {
-                0.0
-            };
+/// Solid k-mers are not tracked by the HyperLogLog sketch, so --estimate follows the distinct estimate instead
+fn tracked_kmers(args: &Args, counter: &KmerCounter) -> u64 {
+    if args.estimate {
+        counter.distinct_kmers()
+    } else {
+        counter.unique_solid_kmers()
+    }
+}
 
-            println!(
-                "Processed {} reads, unique k-mers: {}, Δ_avg: {:.1}, Δ²_avg: {:.1}",
-                reads, kmers, avg_growth, avg_accel
-            );
+/// One k-mer length being counted, with its saturation curve
+struct Track {
+    counter: KmerCounter,
+    tracker: SaturationTracker,
+    /// Distinct k-mers against the input's total, with --two-pass
+    coverage: Option<Coverage>,
+    /// Redundant reads in the latest interval, with --redundancy-stats
+    redundancy: Option<Redundancy>,
+    /// Followed k-mers when the curve started, e.g. from a loaded snapshot
+    curve_start: u64,
+    /// Every sample, with --target-saturation or --curve-out
+    curve: Vec<CurvePoint>,
+}
 
-            // WebSocket message can include acceleration too if desired
-            tx.send((reads, kmers)).await?;
+impl Track {
+    fn new(counter: KmerCounter) -> Self {
+        Track { counter, tracker: SaturationTracker::new(0), coverage: None, redundancy: None, curve_start: 0, curve: Vec::new() }
+    }
 
-            // Auto-stop condition
-            if reads > 50000 && avg_accel.abs() < 20.0 {
-                println!(
-                    "Stopping early: acceleration average {:.1} < 50 after {} reads.",
-                    avg_accel, reads
-                );
-                break;
-            }
+    /// Start this k's curve from the k-mers counted so far
+    fn restart(&mut self, args: &Args) {
+        let kmers = tracked_kmers(args, &self.counter);
+        self.tracker = SaturationTracker::new(kmers);
+        self.tracker.set_history_window(args.history_window);
+        self.curve_start = kmers;
+        self.curve.clear();
+        self.redundancy = self.counter.redundancy().map(Redundancy::new);
+    }
 
-            prev_kmers = kmers;
+    /// Take the next sample of this k's curve, after `reads` reads
+    fn sample(&mut self, args: &Args, reads: u64) -> Sample {
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record(self.counter.distinct_kmers());
+        }
+        if let (Some(redundancy), Some(totals)) = (self.redundancy.as_mut(), self.counter.redundancy()) {
+            redundancy.record(totals);
         }
+        let sample = self.tracker.record(tracked_kmers(args, &self.counter));
+        if args.keeps_curve() {
+            self.curve.push(CurvePoint {
+                reads,
+                distinct_kmers: self.counter.distinct_kmers(),
+                solid_kmers: self.counter.unique_solid_kmers(),
+                kmers: sample.kmers,
+                growth: sample.growth,
+                acceleration: sample.acceleration,
+            });
+        }
+        sample
+    }
+
+    /// Fit the curve and project the reads reaching `target` of its asymptote, `reads` being processed
+    fn project(&self, target: f64, reads: u64) -> Option<Projection> {
+        let samples: Vec<(u64, u64)> = self.curve.iter().map(|point| (point.reads, point.kmers)).collect();
+        let fit = SaturationFit::fit(self.curve_start, &samples)?;
+        let target_reads = fit.reads_for(target).ceil() as u64;
+        Some(Projection {
+            asymptote: fit.asymptote(),
+            half_saturation_reads: fit.half,
+            saturation: fit.kmers_at(reads as f64) / fit.asymptote(),
+            target,
+            target_reads,
+            more_reads: target_reads.saturating_sub(reads),
+        })
+    }
+}
 
-        idx += 1;
+/// --two-pass: distinct k-mers seen so far against their total in the input
+struct Coverage {
+    /// Estimated by the first pass
+    total: u64,
+    /// Distinct k-mers at the latest sample
+    seen: u64,
+    /// New distinct k-mers at the latest sample
+    growth: u64,
+}
+
+impl Coverage {
+    fn new(total: u64) -> Self {
+        Coverage { total, seen: 0, growth: 0 }
+    }
+
+    fn record(&mut self, distinct: u64) {
+        self.growth = distinct.saturating_sub(self.seen);
+        self.seen = distinct;
+    }
+
+    /// Share of the total seen, in percent; the estimate's error can take it past 100
+    fn percent(&self) -> f64 {
+        if self.total == 0 { 100.0 } else { self.seen as f64 / self.total as f64 * 100.0 }
+    }
+}
+
+/// --redundancy-stats: reads whose k-mers were all solid already, between two samples
+struct Redundancy {
+    /// The counter's reads and redundant reads at the previous sample
+    before: (u64, u64),
+    /// Percentage of redundant reads since the previous sample
+    percent: f64,
+}
+
+impl Redundancy {
+    fn new(totals: (u64, u64)) -> Self {
+        Redundancy { before: totals, percent: 0.0 }
+    }
+
+    fn record(&mut self, totals: (u64, u64)) {
+        self.percent = percent(totals.1 - self.before.1, totals.0 - self.before.0);
+        self.before = totals;
+    }
+}
+
+/// `part` as a percentage of `whole`, 0 when `whole` is 0
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 { 0.0 } else { part as f64 / whole as f64 * 100.0 }
+}
+
+/// Build a counter for one k with the settings given on the command line
+fn build_counter(args: &Args, k: usize, threads: usize) -> KmerCounter {
+    let mut counter = KmerCounter::new(k);
+    counter.set_threads(threads);
+    counter.set_concurrent_map(args.concurrent_map);
+    counter.set_count_width(args.count_width);
+    counter.set_min_count(args.min_count);
+    let by_count = matches!(args.sorted, Some(DumpOrder::Count));
+    counter.set_track_counts(
+        args.histogram.is_some() || args.spectrum_svg.is_some() || args.estimate_genome_size || args.top_n.is_some() || by_count,
+    );
+    counter.set_allow_ambiguous(args.allow_ambiguous);
+    counter.set_case_sensitive(args.case_sensitive);
+    counter.set_rna(args.rna);
+    counter.set_forward_only(args.forward_only);
+    counter.set_strand_stats(args.strand_stats);
+    counter.set_protein(args.protein);
+    counter.set_minimizer_window(args.minimizer);
+    counter.set_min_complexity(args.mask_low_complexity.then_some(args.complexity_threshold));
+    counter.set_max_homopolymer(args.max_homopolymer);
+    counter.set_gc_range(args.gc_range());
+    counter.set_track_redundancy(args.redundancy_stats);
+    if let Some(pattern) = &args.seed_pattern {
+        let mask: Vec<bool> = pattern.bytes().map(|b| b == b'1').collect();
+        counter.set_seed_pattern(Some(&mask));
+    }
+    if args.estimate {
+        counter.set_estimate(Some(args.hll_precision));
+    }
+    counter.set_max_memory(args.max_memory.map(|mb| mb << 20));
+    counter.set_nthash(args.nthash);
+    counter.set_hash_seed(args.hash_seed);
+    if args.sketch_out.is_some() || args.matrix.is_some() {
+        counter.set_minhash_size(Some(args.sketch_size));
+    }
+    counter
+}
+
+/// Auto-stop rule: relative growth under --saturation-pct if given (relative to the
+/// input's total distinct k-mers with --two-pass), otherwise flat acceleration
+fn is_saturated(args: &Args, track: &Track) -> bool {
+    let sample = track.tracker.last();
+    match (args.saturation_pct, &track.coverage) {
+        (Some(pct), Some(coverage)) => (coverage.growth as f64) < coverage.total as f64 * pct / 100.0,
+        (Some(pct), None) => (sample.growth as f64) < sample.kmers as f64 * pct / 100.0,
+        (None, _) => sample.avg_accel.abs() < args.stop_accel_threshold,
+    }
+}
+
+/// Check a spaced seed mask: `0`s and `1`s, with at least one `1`
+fn parse_seed_pattern(value: &str) -> Result<String, String> {
+    if !value.bytes().all(|b| b == b'0' || b == b'1') {
+        return Err(format!("{} is not a mask of 0s and 1s", value));
+    }
+    if !value.contains('1') {
+        return Err("a seed pattern must read at least one position".to_string());
+    }
+    Ok(value.to_string())
+}
+
+/// Count width, in bits
+fn parse_count_width(value: &str) -> Result<CountWidth, String> {
+    match value.trim() {
+        "8" => Ok(CountWidth::U8),
+        "16" => Ok(CountWidth::U16),
+        other => Err(format!("{} is not a count width: expected 8 or 16", other)),
+    }
+}
+
+/// k-mer length of at least one base
+fn parse_k(value: &str) -> Result<usize, String> {
+    let k: usize = value.trim().parse().map_err(|e| format!("{}", e))?;
+    if k == 0 {
+        return Err("k must be at least 1: a k-mer needs at least one base".to_string());
+    }
+    Ok(k)
+}
+
+/// Parse a fraction in (0, 1]
+fn parse_fraction(value: &str) -> Result<f64, String> {
+    let fraction: f64 = value.parse().map_err(|e| format!("{}", e))?;
+    if fraction > 0.0 && fraction <= 1.0 {
+        Ok(fraction)
+    } else {
+        Err(format!("{} is not a fraction in (0, 1]", fraction))
+    }
+}
+
+/// Parse a GC fraction, in [0, 1]
+fn parse_gc_fraction(value: &str) -> Result<f64, String> {
+    let fraction: f64 = value.parse().map_err(|e| format!("{}", e))?;
+    if (0.0..=1.0).contains(&fraction) {
+        Ok(fraction)
+    } else {
+        Err(format!("{} is not a fraction between 0 and 1", fraction))
+    }
+}
+
+/// Parse a fraction in (0, 1): a saturating curve never quite reaches its asymptote
+fn parse_target(value: &str) -> Result<f64, String> {
+    let fraction: f64 = value.parse().map_err(|e| format!("{}", e))?;
+    if fraction > 0.0 && fraction < 1.0 {
+        Ok(fraction)
+    } else {
+        Err(format!("{} is not a fraction in (0, 1)", fraction))
+    }
+}
+
+/// Check a tracing filter, as RUST_LOG takes it
+fn parse_log_filter(value: &str) -> Result<String, String> {
+    EnvFilter::builder().parse(value).map_err(|e| e.to_string())?;
+    Ok(value.to_string())
+}
+
+/// Parse a positive byte count, with an optional K/KB or M/MB suffix counted in powers of 1024
+fn parse_size(value: &str) -> Result<usize, String> {
+    let upper = value.trim().to_ascii_uppercase();
+    let digits = upper.trim_end_matches('B');
+    let (number, unit) = match digits.as_bytes().last() {
+        Some(b'K') => (&digits[..digits.len() - 1], 1 << 10),
+        Some(b'M') => (&digits[..digits.len() - 1], 1 << 20),
+        _ => (digits, 1),
+    };
+    let size = number.trim().parse::<usize>().map_err(|e| format!("{}: {}", value, e))?;
+    match size.checked_mul(unit) {
+        Some(0) => Err("a buffer needs at least one byte".to_string()),
+        Some(bytes) => Ok(bytes),
+        None => Err(format!("{} is too large", value)),
+    }
+}
+
+/// Parse a positive rate, such as messages per second
+fn parse_rate(value: &str) -> Result<f64, String> {
+    let rate: f64 = value.parse().map_err(|e| format!("{}", e))?;
+    if rate > 0.0 && rate.is_finite() {
+        Ok(rate)
+    } else {
+        Err(format!("{} is not a positive rate", rate))
+    }
+}
+
+/// Duration with an `s`, `m` or `h` suffix (seconds without one), e.g. `30m` or `1.5h`
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = match value.char_indices().last() {
+        Some((i, 's')) => (&value[..i], 1.0),
+        Some((i, 'm')) => (&value[..i], 60.0),
+        Some((i, 'h')) => (&value[..i], 3600.0),
+        _ => (value, 1.0),
+    };
+    let seconds = number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .map(|number| number * unit)
+        .filter(|&seconds| seconds.is_finite() && seconds > 0.0)
+        .ok_or_else(|| format!("'{}' is not a positive duration such as 90s, 30m or 2h", value))?;
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// Parse a percentage between 0 and 100
+fn parse_percentage(value: &str) -> Result<f64, String> {
+    let pct: f64 = value.parse().map_err(|e| format!("{}", e))?;
+    if (0.0..=100.0).contains(&pct) {
+        Ok(pct)
+    } else {
+        Err(format!("{} is not a percentage between 0 and 100", pct))
+    }
+}
+
+/// Destinations for the statistics taken at each sampling point
+struct Reporter<'a> {
+    args: &'a Args,
+    progress_log: Option<BufWriter<File>>,
+    live: Option<Live>,
+    started: Instant,
+    /// Time, reads and bases at the previous sample
+    last_sample: (Instant, u64, u64),
+    /// Keep the samples to itself, as in the --two-pass estimate pass
+    quiet: bool,
+}
+
+impl Reporter<'_> {
+    /// Log, print and broadcast the latest sample of every track, taken after
+    /// `reads` reads (`bases` bases) of `path`
+    async fn report(
+        &mut self,
+        path: &Path,
+        reads: u64,
+        bases: u64,
+        tracks: &[Track],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.quiet {
+            return Ok(());
+        }
+        let args = self.args;
+        let now = Instant::now();
+        let (last_time, last_reads, last_bases) = self.last_sample;
+        let throughput = Throughput::new(reads - last_reads, bases - last_bases, now - last_time);
+        self.last_sample = (now, reads, bases);
+
+        let file = if args.input.len() > 1 {
+            format!(" [{}]", path.display())
+        } else {
+            String::new()
+        };
+        let rss = if args.report_memory { resident_memory() } else { None };
+        let rss_note = rss.map(|rss| format!(", RSS: {}", megabytes(rss))).unwrap_or_default();
+        let sweep = tracks.len() > 1;
+        if sweep && !args.json {
+            println!("Processed {} reads{}, {}{}", args.count(reads), file, throughput, rss_note);
+        }
+
+        let mut snapshots = Vec::with_capacity(tracks.len());
+        for Track { counter, tracker, coverage, redundancy, .. } in tracks {
+            let sample = tracker.last();
+            let saturation_pct = coverage.as_ref().map(Coverage::percent);
+            let redundant_pct = redundancy.as_ref().map(|redundancy| redundancy.percent);
+            let map_bytes = args.report_memory.then(|| counter.memory_bytes() as u64);
+            let count_bits = counter.count_width().filter(|_| args.report_memory).map(CountWidth::bits);
+            if let Some(log) = self.progress_log.as_mut() {
+                writeln!(
+                    log,
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    counter.k(),
+                    reads,
+                    counter.distinct_kmers(),
+                    counter.unique_solid_kmers(),
+                    sample.growth,
+                    sample.acceleration
+                )?;
+            }
+
+            if args.json {
+                let progress = Progress {
+                    file: path.display().to_string(),
+                    k: counter.k(),
+                    reads,
+                    distinct_kmers: counter.distinct_kmers(),
+                    solid_kmers: counter.unique_solid_kmers(),
+                    total_kmers: counter.total_kmers(),
+                    avg_growth: sample.avg_growth,
+                    avg_accel: sample.avg_accel,
+                    throughput,
+                    map_bytes,
+                    count_bits,
+                    rss_bytes: rss,
+                    estimated_total: coverage.as_ref().map(|coverage| coverage.total),
+                    saturation_pct,
+                    redundant_pct,
+                };
+                println!("{}", serde_json::to_string(&progress)?);
+            } else {
+                let width_note = count_bits.map(|bits| format!(" ({}-bit counts)", bits)).unwrap_or_default();
+                let map_note =
+                    map_bytes.map(|bytes| format!(", map: {}{}", megabytes(bytes), width_note)).unwrap_or_default();
+                let redundancy_note =
+                    redundant_pct.map(|pct| format!(", redundant reads: {:.1}%", pct)).unwrap_or_default();
+                let coverage_note = coverage
+                    .as_ref()
+                    .map(|coverage| format!(" ({:.1}% of ~{})", coverage.percent(), args.count(coverage.total)))
+                    .unwrap_or_default();
+                let (distinct, solid) = (args.count(counter.distinct_kmers()), args.count(counter.unique_solid_kmers()));
+                let counts = if args.estimate {
+                    format!("estimated distinct k-mers: {}", distinct)
+                } else if args.max_memory.is_some() {
+                    format!("estimated distinct k-mers: {}, estimated solid k-mers: {}", distinct, solid)
+                } else {
+                    format!("distinct k-mers: {}{}, solid k-mers: {}", distinct, coverage_note, solid)
+                };
+                let counts = format!("{}, total k-mers: {}", counts, args.count(counter.total_kmers()));
+                let digits = args.precision as usize;
+                if sweep {
+                    println!(
+                        "  k={}: {}, Δ_avg: {:.*}, Δ²_avg: {:.*}{}{}",
+                        counter.k(), counts, digits, sample.avg_growth, digits, sample.avg_accel, redundancy_note, map_note
+                    );
+                } else {
+                    println!(
+                        "Processed {} reads{}, {}, Δ_avg: {:.*}, Δ²_avg: {:.*}{}, {}{}{}",
+                        args.count(reads), file, counts, digits, sample.avg_growth, digits, sample.avg_accel, redundancy_note,
+                        throughput, map_note, rss_note
+                    );
+                }
+            }
+
+            snapshots.push(Snapshot {
+                k: counter.k(),
+                reads,
+                distinct: counter.distinct_kmers(),
+                solid: counter.unique_solid_kmers(),
+                growth: sample.growth,
+                accel: sample.acceleration,
+                rate: throughput.reads_per_sec,
+                map_bytes,
+                count_bits,
+                rss_bytes: rss,
+                saturation_pct,
+                redundant_pct,
+            });
+        }
+        if let Some(log) = self.progress_log.as_mut() {
+            // Flush every sample so `tail -f` follows the run live
+            log.flush()?;
+        }
+
+        if let Some(live) = self.live.as_mut() {
+            live.publish(snapshots);
+        }
+        Ok(())
+    }
+
+    /// Send WebSocket clients the samples --ws-max-rate held back
+    fn flush(&mut self) {
+        if let Some(live) = self.live.as_mut() {
+            live.flush();
+        }
+    }
+
+    /// Tell WebSocket clients counting has ended after `reads` reads, and why
+    async fn finish(&mut self, reason: &'static str, reads: u64) {
+        if let Some(live) = self.live.as_mut() {
+            live.finish(reason, reads).await;
+        }
+    }
+
+    /// Start timing a new pass over the reads, whose counts restart from zero
+    fn restart(&mut self) {
+        self.started = Instant::now();
+        self.last_sample = (self.started, 0, 0);
+    }
+
+    /// Throughput since the reporter was created
+    fn overall(&self, reads: u64, bases: u64) -> Throughput {
+        Throughput::new(reads, bases, self.started.elapsed())
+    }
+}
+
+/// Resident set size of this process in bytes, where the OS exposes it (Linux `/proc`)
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.trim_start_matches("VmRSS:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kb * 1024)
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1 << 20) as f64)
+}
+
+/// `n` with a comma between every three digits, whatever the locale
+fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut text = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            text.push(',');
+        }
+        text.push(digit);
+    }
+    text
+}
+
+/// Open the progress log for appending, writing the header row if the file is new or empty
+fn open_progress_log(path: &Path) -> std::io::Result<BufWriter<File>> {
+    let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    let is_empty = file.metadata()?.len() == 0;
+    let mut log = BufWriter::new(file);
+    if is_empty {
+        writeln!(log, "k\treads\tdistinct\tsolid\tgrowth\taccel")?;
+    }
+    Ok(log)
+}
+
+/// Write the k-mer spectrum as two tab-separated columns without a header,
+/// the format GenomeScope and `jellyfish histo` use
+fn write_histogram(path: &Path, histogram: &[(u16, u64)]) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for (abundance, kmers) in histogram {
+        writeln!(out, "{}\t{}", abundance, kmers)?;
+    }
+    out.flush()
+}
+
+/// Write the samples of every track's curve as one Parquet table, a row per sample and k,
+/// with the columns of the --progress-log
+#[cfg(feature = "parquet")]
+fn write_parquet(path: &Path, tracks: &[Track]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use arrow_array::{ArrayRef, Int64Array, RecordBatch, UInt32Array, UInt64Array};
+    use arrow_schema::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+
+    let points = || tracks.iter().flat_map(|track| track.curve.iter().map(|point| (track.counter.k(), point)));
+    let unsigned = |value: fn(&CurvePoint) -> u64| -> ArrayRef {
+        Arc::new(UInt64Array::from_iter_values(points().map(|(_, point)| value(point))))
+    };
+    let signed = |value: fn(&CurvePoint) -> i64| -> ArrayRef {
+        Arc::new(Int64Array::from_iter_values(points().map(|(_, point)| value(point))))
+    };
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("k", DataType::UInt32, false),
+        Field::new("reads", DataType::UInt64, false),
+        Field::new("distinct", DataType::UInt64, false),
+        Field::new("solid", DataType::UInt64, false),
+        Field::new("growth", DataType::Int64, false),
+        Field::new("accel", DataType::Int64, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(UInt32Array::from_iter_values(points().map(|(k, _)| k as u32))),
+            unsigned(|point| point.reads),
+            unsigned(|point| point.distinct_kmers),
+            unsigned(|point| point.solid_kmers),
+            signed(|point| point.growth),
+            signed(|point| point.acceleration),
+        ],
+    )?;
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Write solid k-mers one per line, or as FASTA records named by index and count
+fn dump_kmers(path: &Path, counter: &KmerCounter, fasta: bool, order: Option<DumpOrder>) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let mut index = 0u64;
+    let mut write = |kmer: &[u8], count: u16| {
+        if fasta {
+            index += 1;
+            writeln!(out, ">kmer_{} count={}", index, count)?;
+        }
+        out.write_all(kmer)?;
+        out.write_all(b"\n")
+    };
+    match order {
+        None => {
+            let mut result = Ok(());
+            counter.for_each_solid_kmer(|kmer, count| {
+                if result.is_ok() {
+                    result = write(kmer, count);
+                }
+            });
+            result?;
+        }
+        Some(order) => {
+            // All keys have the same length, so they are packed back to back
+            // rather than allocated one by one
+            let mut keys = Vec::new();
+            let mut entries = Vec::new();
+            let mut key_len = 0;
+            counter.for_each_solid_kmer(|kmer, count| {
+                key_len = kmer.len();
+                entries.push((entries.len(), count));
+                keys.extend_from_slice(kmer);
+            });
+            let key = |i: usize| &keys[i * key_len..(i + 1) * key_len];
+            match order {
+                DumpOrder::Kmer => entries.par_sort_unstable_by(|&(a, _), &(b, _)| key(a).cmp(key(b))),
+                DumpOrder::Count => entries.par_sort_unstable_by(|&(a, count_a), &(b, count_b)| {
+                    count_b.cmp(&count_a).then_with(|| key(a).cmp(key(b)))
+                }),
+            }
+            for &(i, count) in &entries {
+                write(key(i), count)?;
+            }
+        }
+    }
+    out.flush()
+}
+
+/// Totals of one pass over some inputs
+struct Run {
+    /// Reads counted, after --take-every/--subsample
+    reads: u64,
+    /// Bases of the counted reads, before trimming
+    bases: u64,
+    /// Reads shorter than --min-len (after trimming), which were not counted
+    short_reads: u64,
+    /// Bases matching an --adapters sequence, left out of the counted windows
+    adapter_bases: u64,
+    /// --dedup: reads skipped as duplicates of one counted before
+    duplicate_reads: Option<u64>,
+    /// Records skipped with --skip-errors
+    malformed_records: u64,
+    /// --interleaved: pairs counted
+    pairs: Option<u64>,
+    stop: Stop,
+    /// File the last read came from
+    last_file: PathBuf,
+    /// Composition of the counted reads, with --seq-stats
+    seq_stats: Option<SeqStats>,
+    /// Counted reads holding bytes other than A/C/G/T/U, unless counting peptides
+    non_acgt: Option<NonAcgt>,
+    /// What each input file added, when several were read one after the other
+    per_file: Option<Vec<FileContribution>>,
+}
+
+/// Why reading stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stop {
+    EndOfInput,
+    /// Auto-stop: every curve saturated
+    Saturated,
+    MaxReads,
+    /// --max-distinct
+    MaxDistinct,
+    /// --max-memory-mb
+    MemoryCapped,
+    /// --max-time
+    TimeLimit,
+    /// Ctrl-C or a WebSocket abort
+    Interrupted,
+}
+
+impl Stop {
+    /// As the final WebSocket event names it
+    fn name(self) -> &'static str {
+        match self {
+            Stop::EndOfInput => "end_of_input",
+            Stop::Saturated => "saturated",
+            Stop::MaxReads => "max_reads",
+            Stop::MaxDistinct => "max_distinct",
+            Stop::MemoryCapped => "memory_capped",
+            Stop::TimeLimit => "time_limit",
+            Stop::Interrupted => "interrupted",
+        }
+    }
+
+    /// Whether the run as a whole has to stop, rather than only this pass over the reads
+    fn ends_run(self) -> bool {
+        matches!(self, Stop::TimeLimit | Stop::Interrupted)
+    }
+}
+
+/// Reads, bases and the k-mers of every track up to some point of the input
+struct Totals {
+    reads: u64,
+    bases: u64,
+    distinct: Vec<u64>,
+    solid: Vec<u64>,
+}
+
+impl Totals {
+    fn of(reads: u64, bases: u64, tracks: &[Track]) -> Self {
+        Totals {
+            reads,
+            bases,
+            distinct: tracks.iter().map(|track| track.counter.distinct_kmers()).collect(),
+            solid: tracks.iter().map(|track| track.counter.unique_solid_kmers()).collect(),
+        }
+    }
+
+    /// What was added since `start`, credited to `file`
+    fn since(&self, start: &Totals, file: &Path) -> FileContribution {
+        let added = |now: &[u64], before: &[u64]| now.iter().zip(before).map(|(now, before)| now.saturating_sub(*before)).collect();
+        FileContribution {
+            file: file.to_path_buf(),
+            reads: self.reads - start.reads,
+            bases: self.bases - start.bases,
+            new_distinct: added(&self.distinct, &start.distinct),
+            new_solid: added(&self.solid, &start.solid),
+        }
+    }
+}
+
+/// Reads, bases and new k-mers of one input file, with new k-mers per track
+struct FileContribution {
+    file: PathBuf,
+    reads: u64,
+    bases: u64,
+    new_distinct: Vec<u64>,
+    new_solid: Vec<u64>,
+}
+
+/// Parse failures in a row after which --skip-errors drops the rest of an input: far more
+/// than one damaged record yields, so its stream is likely broken, e.g. truncated
+const MAX_CONSECUTIVE_ERRORS: u32 = 1000;
+
+/// --dedup: hashes of the reads counted, to skip exact duplicates of them
+#[derive(Default)]
+struct Dedup {
+    seen: FxHashSet<u64>,
+    duplicates: u64,
+}
+
+impl Dedup {
+    /// Whether `seq` repeats a read seen before; it is remembered otherwise. Two distinct
+    /// reads share a hash with odds of about n² / 2^65 over n reads, negligible for QC.
+    fn is_duplicate(&mut self, seq: &[u8]) -> bool {
+        let duplicate = !self.seen.insert(murmur3_64(seq, MINHASH_SEED));
+        self.duplicates += duplicate as u64;
+        duplicate
+    }
+}
+
+/// Records that failed to parse. The parser resumes at the next line, so a damaged record
+/// can fail several times in a row; such a run of failures counts as one skipped record.
+#[derive(Default)]
+struct Malformed {
+    /// Runs of failures, each ended by a record that parsed or the end of an input
+    skipped: u64,
+    /// Failures since the last record that parsed
+    failures: u32,
+    /// Skipped records of the current input, to number its records
+    in_file: u64,
+}
+
+impl Malformed {
+    fn start_file(&mut self) {
+        self.failures = 0;
+        self.in_file = 0;
+    }
+
+    fn parsed(&mut self) {
+        self.failures = 0;
+    }
+
+    /// A record of `path` failed to parse after `parsed` good ones. Without --skip-errors
+    /// the error ends the run; with it the record is skipped (true), or after too many
+    /// failures in a row the rest of the input is (false). Paired inputs of different
+    /// lengths always end the run.
+    fn fail(
+        &mut self,
+        args: &Args,
+        path: &Path,
+        parsed: u64,
+        error: Box<dyn std::error::Error + Send + Sync>,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        if error.is::<MateCountError>() {
+            error!(file = %path.display(), error = %error, "paired inputs differ in length");
+            return Err(error);
+        }
+        let record = parsed + self.in_file + (self.failures == 0) as u64;
+        if !args.skip_errors {
+            error!(file = %path.display(), record, error = %error, "cannot parse record");
+            // Paired inputs already name the file and record
+            return Err(if error.is::<RecordError>() { error } else { RecordError::new(path, record, error).into() });
+        }
+        if self.failures == 0 {
+            warn!(file = %path.display(), record, error = %error, "skipping malformed record");
+            self.skipped += 1;
+            self.in_file += 1;
+        }
+        self.failures += 1;
+        if self.failures >= MAX_CONSECUTIVE_ERRORS {
+            note!(
+                args,
+                "Warning: {} failed to parse {} times in a row after record {} ({}); skipping the rest of it.",
+                path.display(), MAX_CONSECUTIVE_ERRORS, parsed + self.in_file - 1, error
+            );
+            return Ok(false);
+        }
+        Ok(true)
+    }
+}
+
+/// FASTQ reads needed before their qualities can suggest the input is Phred+64
+const PHRED64_HINT_READS: u64 = 1000;
+
+/// Rebase the qualities of `record` to the Phred+33 the quality options expect, when
+/// they read them, failing when a quality is out of range for the input's encoding
+fn check_quality(args: &Args, record: &mut SeqRecord) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !args.uses_quality() {
+        return Ok(());
+    }
+    let offset = if args.phred64 { PHRED64_OFFSET } else { PHRED_OFFSET };
+    record.rebase_quality(offset).map_err(|e| match args.phred64 {
+        true => format!("{}; the input is probably Phred+33, drop --phred64", e).into(),
+        false => e.into(),
+    })
+}
+
+/// Records of one input, as they are read
+type Records<'a> = Box<dyn Iterator<Item = Result<SeqRecord, Box<dyn std::error::Error + Send + Sync>>> + 'a>;
+
+/// Group `records` into the units thinning and sampling pick from: reads, or the two mates
+/// of each pair when they are paired, the last record of an odd input alone. Errors pass
+/// through as they come, leaving the mates of a pair before them waiting for the rest.
+fn read_units<'a>(
+    args: &Args,
+    mut records: Records<'a>,
+) -> impl Iterator<Item = Result<Vec<SeqRecord>, Box<dyn std::error::Error + Send + Sync>>> + 'a {
+    let size = if args.paired() { 2 } else { 1 };
+    let mut unit = Vec::with_capacity(size);
+    std::iter::from_fn(move || {
+        while unit.len() < size {
+            match records.next() {
+                Some(Ok(record)) => unit.push(record),
+                Some(Err(e)) => return Some(Err(e)),
+                None if unit.is_empty() => return None,
+                None => break,
+            }
+        }
+        Some(Ok(std::mem::replace(&mut unit, Vec::with_capacity(size))))
+    })
+}
+
+/// [`check_quality`] on every read of `unit`, numbered after the `parsed` records of
+/// `path` before it, returning how many passed: a pair is only counted when both mates
+/// do, so a mate failing drops the other with it
+fn check_unit(
+    args: &Args,
+    path: &Path,
+    unit: &mut [SeqRecord],
+    mut parsed: u64,
+    malformed: &mut Malformed,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let first = parsed;
+    for record in unit.iter_mut() {
+        // The record itself parsed, so it is skipped alone without ending a run of failures
+        malformed.parsed();
+        match check_quality(args, record) {
+            Ok(()) => parsed += 1,
+            Err(e) => {
+                malformed.fail(args, path, parsed, e)?;
+            }
+        }
+    }
+    Ok(parsed - first)
+}
+
+/// Open an input, or with --input-1/--input-2 the pair of files it stands for, whose
+/// mates then alternate as in an interleaved input
+fn open_records<'a>(args: &'a Args, path: &Path) -> Result<Records<'a>, Box<dyn std::error::Error + Send + Sync>> {
+    let case_sensitive = args.case_sensitive;
+    let open = |path: &Path| match args.mmap {
+        true => open_mapped_with_capacity(path, args.buffer_size),
+        false => open_reader_with_capacity(path, args.buffer_size),
+    };
+    match &args.input_2 {
+        Some(second) => {
+            let mut reader = PairedReader::new([open(path)?, open(second)?], [path, second]);
+            let pairs = std::iter::from_fn(move || reader.next_pair(case_sensitive));
+            Ok(Box::new(pairs.flat_map(|pair| match pair {
+                Ok((first, second)) => vec![Ok(first), Ok(second)],
+                Err(e) => vec![Err(e)],
+            })))
+        }
+        None => {
+            let mut reader = open(path)?;
+            Ok(Box::new(std::iter::from_fn(move || reader.next_record(case_sensitive))))
+        }
+    }
+}
+
+/// [`open_records`] on a reader thread of its own, so decompression and parsing
+/// overlap the counting: it sends the records in batches of `BATCH_SIZE` over a
+/// bounded channel, and waits while `READ_AHEAD_BATCHES` are left to count. The
+/// thread stops once the records are dropped.
+fn read_ahead(args: &Args, path: &Path) -> Result<Records<'static>, Box<dyn std::error::Error + Send + Sync>> {
+    let (opened_tx, opened) = mpsc::sync_channel(1);
+    let (batches_tx, batches) = mpsc::sync_channel::<Vec<_>>(READ_AHEAD_BATCHES);
+    let (args, path) = (args.clone(), path.to_path_buf());
+    std::thread::Builder::new().name("reader".into()).spawn(move || {
+        let records = match open_records(&args, &path) {
+            Ok(records) => records,
+            Err(e) => {
+                let _ = opened_tx.send(Err(e));
+                return;
+            }
+        };
+        let _ = opened_tx.send(Ok(()));
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        for record in records {
+            batch.push(record);
+            if batch.len() >= BATCH_SIZE && batches_tx.send(std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE))).is_err() {
+                return;
+            }
+        }
+        let _ = batches_tx.send(batch);
+    })?;
+    // Only a panic while opening leaves no answer
+    opened.recv().unwrap_or_else(|_| Err("the reader thread stopped".into()))?;
+    Ok(Box::new(batches.into_iter().flatten()))
+}
+
+/// One input checked by --validate, as printed with --json
+#[derive(Default, Serialize)]
+struct Validation {
+    file: String,
+    records: u64,
+    bases: u64,
+    /// Runs of parse failures, counted as for --skip-errors, and failures to open the input
+    malformed_records: u64,
+}
+
+/// --validate: read every record of every input, printing each malformed one, and
+/// return whether all of them parsed
+fn validate_inputs(args: &Args) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    let offset = if args.phred64 { PHRED64_OFFSET } else { PHRED_OFFSET };
+    let mut valid = true;
+    let mut report = |validation: &Validation| -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        valid &= validation.malformed_records == 0;
+        if args.json {
+            println!("{}", serde_json::to_string(validation)?);
+        } else {
+            println!(
+                "{}: {} records, {} bases, {} malformed",
+                validation.file,
+                args.count(validation.records),
+                args.count(validation.bases),
+                args.count(validation.malformed_records)
+            );
+        }
+        Ok(())
+    };
+    for path in &args.input {
+        // Paired inputs are checked together, their records interleaved
+        let file = match &args.input_2 {
+            Some(second) => format!("{} + {}", path.display(), second.display()),
+            None => path.display().to_string(),
+        };
+        let mut validation = Validation { file, ..Validation::default() };
+        let records = match open_records(args, path) {
+            Ok(records) => records,
+            Err(e) => {
+                eprintln!("Cannot open {}: {}", path.display(), e);
+                validation.malformed_records = 1;
+                report(&validation)?;
+                continue;
+            }
+        };
+        // As with --skip-errors, a run of failures is one damaged record
+        let mut failures = 0;
+        for record in records {
+            let error = match record {
+                Ok(mut record) => match record.rebase_quality(offset) {
+                    Ok(()) => {
+                        validation.records += 1;
+                        validation.bases += record.seq.len() as u64;
+                        failures = 0;
+                        continue;
+                    }
+                    Err(e) => e.into(),
+                },
+                Err(e) => e,
+            };
+            let paired_mismatch = error.is::<MateCountError>();
+            if failures == 0 {
+                validation.malformed_records += 1;
+                let record = validation.records + validation.malformed_records;
+                match error.is::<RecordError>() || paired_mismatch {
+                    true => eprintln!("{}", error),
+                    false => eprintln!("{}", RecordError::new(path, record, error)),
+                }
+            }
+            failures += 1;
+            if paired_mismatch || failures >= MAX_CONSECUTIVE_ERRORS {
+                eprintln!("Giving up on the rest of {}.", path.display());
+                break;
+            }
+        }
+        report(&validation)?;
+    }
+    Ok(valid)
+}
+
+/// Draw the --reservoir sample of `size` reads, or pairs with --interleaved, from all
+/// inputs (Algorithm R), in input order. Ctrl-C ends the draw early, with the reads sampled so far.
+fn reservoir_sample(
+    args: &Args,
+    size: usize,
+    malformed: &mut Malformed,
+    control: &Control,
+) -> Result<Vec<SeqRecord>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    // Each entry is a read, or a pair of them, with its position among the entries seen
+    let mut sample: Vec<(u64, Vec<SeqRecord>)> = Vec::with_capacity(size.min(BATCH_SIZE));
+    let mut seen = 0u64;
+    let mut offer = |unit: Vec<SeqRecord>, seen: &mut u64| {
+        if sample.len() < size {
+            sample.push((*seen, unit));
+        } else {
+            let slot = rng.random_range(0..=*seen);
+            if slot < size as u64 {
+                sample[slot as usize] = (*seen, unit);
+            }
+        }
+        *seen += 1;
+    };
+    let mut records = 0u64;
+    'files: for path in &args.input {
+        let reader = open_records(args, path)?;
+        let first = records;
+        malformed.start_file();
+        for unit in read_units(args, reader) {
+            if control.interrupted() || control.timed_out() {
+                break 'files;
+            }
+            let mut unit = match unit {
+                Ok(unit) => unit,
+                Err(e) => {
+                    if malformed.fail(args, path, records - first, e)? {
+                        continue;
+                    }
+                    break;
+                }
+            };
+            let passed = check_unit(args, path, &mut unit, records - first, malformed)?;
+            records += passed;
+            if passed == unit.len() as u64 {
+                offer(unit, &mut seen);
+            }
+        }
+    }
+    let unit = if args.paired() { "pairs" } else { "reads" };
+    note!(args, "Sampled {} of {} {}.", sample.len(), seen, unit);
+    sample.sort_unstable_by_key(|&(index, _)| index);
+    Ok(sample.into_iter().flat_map(|(_, unit)| unit).collect())
+}
+
+/// Count the reads of `inputs`, or of the `reservoir` sample drawn from them, into
+/// every track, sampling and reporting the curves as they grow, until the reads end,
+/// the curves saturate, --max-reads or --max-memory-mb is reached, or Ctrl-C or a WebSocket
+/// `abort` interrupts `control`; a WebSocket `pause` holds the loop until `resume`
+async fn count_reads(
+    args: &Args,
+    inputs: &[PathBuf],
+    mut reservoir: Option<Vec<SeqRecord>>,
+    tracks: &mut [Track],
+    reporter: &mut Reporter<'_>,
+    control: &Control,
+) -> Result<Run, Box<dyn std::error::Error + Send + Sync>> {
+    // Reads processed, continuing across input files so the curve stays continuous
+    let mut idx = 0u64;
+    // Records read, including those thinned out by --take-every/--subsample
+    let mut records_read = 0u64;
+    // Reads, or pairs with --interleaved, that --take-every/--subsample chose from
+    let mut units_read = 0u64;
+    // --interleaved: pairs processed
+    let mut pairs = 0u64;
+    // Bases of the processed reads, before trimming
+    let mut bases = 0u64;
+    let mut sampled_at = 0u64;
+
+    let mut stop = Stop::EndOfInput;
+    let min_len = args.min_len();
+    // Seeded, so the same --seed and fraction always keep the same reads
+    let mut subsample = args.subsample.map(|fraction| (fraction, StdRng::seed_from_u64(args.seed)));
+    let mut short_reads = 0u64;
+    let adapters = args
+        .adapters
+        .as_deref()
+        .map(|path| {
+            Adapters::open(path, args.case_sensitive)
+                .map_err(|e| format!("Cannot read the adapters in {}: {}", path.display(), e))
+        })
+        .transpose()?;
+    let mut adapter_bases = 0u64;
+    let mut malformed = Malformed::default();
+    // Lowest quality byte of the FASTQ reads the quality options read, to spot Phred+64 input
+    let mut lowest_quality = u8::MAX;
+    let mut fastq_reads = 0u64;
+    let mut seq_stats = args.seq_stats.then(SeqStats::new);
+    let mut non_acgt = (!args.protein).then(NonAcgt::new);
+    let mut dedup = args.dedup.then(Dedup::default);
+    let mut batch: Vec<Vec<u8>> = Vec::with_capacity(BATCH_SIZE);
+
+    // File the last read came from, named in the final sample
+    let mut current = inputs[0].as_path();
+    // Counts are split at file boundaries to tell what each file added
+    let mut per_file = (reservoir.is_none() && inputs.len() > 1).then(Vec::new);
+    let mut file_start = Totals::of(0, 0, tracks);
+
+    'files: for (i, path) in inputs.iter().enumerate() {
+        if let Some(files) = per_file.as_mut().filter(|_| i > 0) {
+            for track in tracks.iter_mut() {
+                track.counter.add_sequences(&batch);
+            }
+            batch.clear();
+            let totals = Totals::of(idx, bases, tracks);
+            files.push(totals.since(&file_start, current));
+            file_start = totals;
+        }
+        current = path;
+        // Not entered: events name it as their parent, since the loop awaits the reporter
+        let span = info_span!("input", file = %path.display());
+        let (file_records, file_reads) = (records_read, idx);
+        // Records of this input that parsed, mates dropped with their pair included
+        let mut file_mates = 0u64;
+        malformed.start_file();
+        // The sample stands for all inputs, and is reported under the first
+        let sampled = reservoir.is_some();
+        let records: Records<'_> = match reservoir.take() {
+            Some(sample) => {
+                info!(parent: &span, reads = sample.len(), "counting the reservoir sample");
+                Box::new(sample.into_iter().map(Ok))
+            }
+            None => {
+                let records = read_ahead(args, path)
+                    .inspect_err(|e| error!(parent: &span, file = %path.display(), error = %e, "cannot open input"))?;
+                info!(parent: &span, "reading input");
+                if args.input.len() > 1 {
+                    note!(args, "Reading {}", path.display());
+                }
+                records
+            }
+        };
+
+        for unit in read_units(args, records) {
+            if control.paused() {
+                note!(args, "Paused after {} reads.", args.count(idx));
+                control.wait_while_paused();
+                if !control.interrupted() {
+                    note!(args, "Resumed.");
+                }
+            }
+            if control.interrupted() {
+                note!(args, "Interrupted after {} reads, writing results.", args.count(idx));
+                stop = Stop::Interrupted;
+                break 'files;
+            }
+            if control.timed_out() {
+                note!(args, "Reached --max-time after {} reads, writing results.", args.count(idx));
+                stop = Stop::TimeLimit;
+                break 'files;
+            }
+            let mut unit = match unit {
+                Ok(unit) => unit,
+                Err(e) => {
+                    if malformed.fail(args, path, records_read - file_records, e)? {
+                        continue;
+                    }
+                    break;
+                }
+            };
+            file_mates += unit.len() as u64;
+            // The reservoir pass already checked the sample
+            let passed = match sampled {
+                true => {
+                    malformed.parsed();
+                    unit.len() as u64
+                }
+                false => check_unit(args, path, &mut unit, records_read - file_records, &mut malformed)?,
+            };
+            records_read += passed;
+            if passed < unit.len() as u64 {
+                continue;
+            }
+            for qual in unit.iter().filter_map(|record| record.qual.as_deref()).filter(|_| args.uses_quality()) {
+                fastq_reads += 1;
+                lowest_quality = qual.iter().fold(lowest_quality, |lowest, &q| lowest.min(q));
+            }
+            // Thinning picks whole pairs
+            let picked = units_read;
+            units_read += 1;
+            if args.take_every.is_some_and(|n| !picked.is_multiple_of(n))
+                || subsample.as_mut().is_some_and(|(fraction, rng)| !rng.random_bool(*fraction))
+            {
+                continue;
+            }
+            if args.paired() {
+                pairs += 1;
+            }
+            let mates = unit.len();
+            for (mate, mut record) in unit.into_iter().enumerate() {
+                if dedup.as_mut().is_some_and(|dedup| dedup.is_duplicate(&record.seq)) {
+                    continue;
+                }
+                // Samples and --max-reads wait for the second mate
+                let mid_pair = mate + 1 < mates;
+                // Reads shorter than k hold no k-mers, which a wrong --k makes true of every read
+                let largest_k = tracks.iter().map(|track| track.counter.k()).max().unwrap_or(0);
+                if idx == 0 && !reporter.quiet && record.seq.len() < largest_k {
+                    note!(
+                        args,
+                        "Warning: the first read is {} bases long, shorter than k = {}; reads shorter than k hold no k-mers and are skipped.",
+                        record.seq.len(), largest_k
+                    );
+                }
+                bases += record.seq.len() as u64;
+                if let Some(stats) = seq_stats.as_mut() {
+                    stats.add(&record.seq);
+                }
+                if let Some(tally) = non_acgt.as_mut() {
+                    tally.add(&record.seq);
+                }
+                if let Some(trim_qual) = args.trim_qual {
+                    record.trim(trim_qual, args.trim_window);
+                }
+
+                // A loaded snapshot already fixed the alphabet
+                if idx == 0
+                    && args.load.is_none()
+                    && !args.protein
+                    && !tracks[0].counter.is_rna()
+                    && looks_like_rna(&record.seq)
+                {
+                    note!(args, "First record looks like RNA, pairing U with A.");
+                    for track in tracks.iter_mut() {
+                        track.counter.set_rna(true);
+                    }
+                }
+
+                if record.seq.len() < min_len {
+                    short_reads += 1;
+                } else if adapters.is_none() && args.min_qual.is_none() {
+                    batch.push(record.seq);
+                } else {
+                    // Low-quality bases and adapters split the read so no counted window spans them
+                    let mut runs = match args.min_qual {
+                        Some(min_qual) => record.quality_ranges(min_qual),
+                        None => {
+                            let whole = 0..record.seq.len();
+                            vec![whole]
+                        }
+                    };
+                    if let Some(adapters) = &adapters {
+                        adapter_bases += adapters.mask(&record.seq, &mut runs);
+                    }
+                    batch.extend(runs.into_iter().map(|run| record.seq[run].to_vec()));
+                }
+                idx += 1;
+                let sample_now = idx - sampled_at >= args.report_every && !mid_pair;
+                // --max-distinct counts each read (or pair) at once, so it stops on the one reaching the cap
+                let count_now = args.max_distinct.is_some() && !mid_pair;
+                if sample_now || count_now || batch.len() >= BATCH_SIZE {
+                    trace!(parent: &span, reads = idx, sequences = batch.len(), "counting batch");
+                    for track in tracks.iter_mut() {
+                        track.counter.add_sequences(&batch);
+                    }
+                    // Stop while the next batch of this size could still not push the maps
+                    // past the cap, rather than once a table has already grown over it
+                    if let Some(cap) = args.max_memory_mb {
+                        let next_kmers: usize = batch.iter().map(Vec::len).sum();
+                        let peak: usize = tracks.iter().map(|track| track.counter.peak_memory_bytes(next_kmers)).sum();
+                        if peak > cap << 20 {
+                            let used: usize = tracks.iter().map(|track| track.counter.memory_bytes()).sum();
+                            note!(
+                                args,
+                                "Warning: the k-mer map (about {}) would grow past --max-memory-mb {}; stopping after {} reads.",
+                                megabytes(used as u64), cap, args.count(idx)
+                            );
+                            stop = Stop::MemoryCapped;
+                            batch.clear();
+                            break 'files;
+                        }
+                    }
+                    batch.clear();
+                }
+
+                if sample_now {
+                    for track in tracks.iter_mut() {
+                        track.sample(args, idx);
+                    }
+                    reporter.report(path, idx, bases, tracks).await?;
+                    sampled_at = idx;
+                    debug!(parent: &span, reads = idx, bases, "sampled");
+
+                    // Auto-stop condition, once every k has saturated; comparisons need whole inputs
+                    if !args.no_auto_stop
+                        && !args.jaccard
+                        && args.matrix.is_none()
+                        && idx > args.stop_min_reads
+                        && tracks.iter().all(|track| is_saturated(args, track))
+                    {
+                        if let [track] = &tracks[..] {
+                            let sample = track.tracker.last();
+                            match (args.saturation_pct, &track.coverage) {
+                                (Some(pct), Some(coverage)) => note!(
+                                    args,
+                                    "Stopping early: last interval added {} distinct k-mers, under {}% of the estimated {} in the input, after {} reads.",
+                                    args.count(coverage.growth), pct, args.count(coverage.total), args.count(idx)
+                                ),
+                                (Some(pct), None) => note!(
+                                    args,
+                                    "Stopping early: last interval added {} k-mers, under {}% of {}, after {} reads.",
+                                    args.change(sample.growth), pct, args.count(sample.kmers), args.count(idx)
+                                ),
+                                (None, _) => note!(
+                                    args,
+                                    "Stopping early: |acceleration average| {:.1} < {} after {} reads.",
+                                    sample.avg_accel.abs(), args.stop_accel_threshold, args.count(idx)
+                                ),
+                            }
+                        } else {
+                            note!(args, "Stopping early: every k has saturated after {} reads.", args.count(idx));
+                        }
+                        stop = Stop::Saturated;
+                        break 'files;
+                    }
+                }
+
+                if args.max_reads.is_some_and(|max| idx >= max) && !mid_pair {
+                    note!(args, "Reached --max-reads after {} reads.", args.count(idx));
+                    stop = Stop::MaxReads;
+                    break 'files;
+                }
+                let capped = |max: &u64| !mid_pair && tracks.iter().all(|track| tracked_kmers(args, &track.counter) >= *max);
+                if let Some(max) = args.max_distinct.filter(capped) {
+                    note!(args, "Reached --max-distinct {} after {} reads.", args.count(max), args.count(idx));
+                    stop = Stop::MaxDistinct;
+                    break 'files;
+                }
+            }
+        }
+        info!(parent: &span, records = records_read - file_records, reads = idx - file_reads, "finished input");
+        if args.paired() && !sampled && !file_mates.is_multiple_of(2) {
+            note!(
+                args,
+                "Warning: {} holds an odd number of records ({}); its last read has no mate.",
+                path.display(), file_mates
+            );
+        }
+        if sampled {
+            break;
+        }
+    }
+    for track in tracks.iter_mut() {
+        track.counter.add_sequences(&batch);
+    }
+    if let Some(files) = per_file.as_mut() {
+        files.push(Totals::of(idx, bases, tracks).since(&file_start, current));
+    }
+
+    // Always end on a sample of the complete input, whatever the interval
+    if idx > sampled_at {
+        for track in tracks.iter_mut() {
+            track.sample(args, idx);
+        }
+        reporter.report(current, idx, bases, tracks).await?;
+    }
+    reporter.flush();
+    // Phred+33 data has low-quality bases; Phred+64 data read as Phred+33 has none under Q31
+    if !args.phred64 && fastq_reads >= PHRED64_HINT_READS && lowest_quality >= PHRED64_OFFSET {
+        note!(
+            args,
+            "Warning: no quality below {:?} (Phred+33 Q{}) in {} FASTQ reads; if the input is Phred+64 (Illumina 1.3 to 1.7), pass --phred64.",
+            PHRED64_OFFSET as char, PHRED64_OFFSET - PHRED_OFFSET, args.count(fastq_reads)
+        );
+    }
+    info!(reads = idx, bases, stop = stop.name(), "counting finished");
+
+    Ok(Run {
+        reads: idx,
+        bases,
+        short_reads,
+        adapter_bases,
+        duplicate_reads: dedup.map(|dedup| dedup.duplicates),
+        malformed_records: malformed.skipped,
+        pairs: args.paired().then_some(pairs),
+        stop,
+        last_file: current.to_path_buf(),
+        seq_stats,
+        non_acgt,
+        per_file,
+    })
+}
+
+/// Count one input into a fresh k-mer set, for the modes comparing inputs
+async fn count_input(
+    args: &Args,
+    path: &Path,
+    threads: usize,
+    reporter: &mut Reporter<'_>,
+    control: &Control,
+) -> Result<(KmerCounter, Run), Box<dyn std::error::Error + Send + Sync>> {
+    let mut tracks = [Track::new(build_counter(args, args.ks()[0], threads))];
+    tracks[0].restart(args);
+    reporter.restart();
+    let run = count_reads(args, &[path.to_path_buf()], None, &mut tracks, reporter, control).await?;
+    let [Track { counter, .. }] = tracks;
+    note!(
+        args,
+        "{}: {} reads, {} distinct k-mers, {} solid",
+        path.display(),
+        args.count(run.reads),
+        args.count(counter.distinct_kmers()),
+        args.count(counter.unique_solid_kmers())
+    );
+    if args.skip_errors {
+        note!(args, "{}: skipped {} malformed records", path.display(), args.count(run.malformed_records));
+    }
+    if let Some(stats) = &run.seq_stats {
+        print_seq_stats(args, stats);
+    }
+    Ok((counter, run))
+}
+
+/// --jaccard: count each input into its own k-mer set, then compare their solid k-mers
+async fn compare_inputs(
+    args: &Args,
+    threads: usize,
+    reporter: &mut Reporter<'_>,
+    control: &Control,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let k = args.ks()[0];
+    let mut counters = Vec::with_capacity(args.input.len());
+    let mut stop = Stop::EndOfInput;
+    let mut reads = 0;
+    for path in &args.input {
+        let (counter, run) = count_input(args, path, threads, reporter, control).await?;
+        counters.push(counter);
+        reads += run.reads;
+        if run.stop.ends_run() {
+            stop = run.stop;
+            break;
+        }
+    }
+    reporter.finish(stop.name(), reads).await;
+    let [a, b] = &counters[..] else {
+        // Interrupted during the first input
+        return Ok(());
+    };
+
+    let intersection = a.shared_solid_kmers(b);
+    let union = a.unique_solid_kmers() + b.unique_solid_kmers() - intersection;
+    let jaccard = if union > 0 { intersection as f64 / union as f64 } else { 0.0 };
+    if args.json {
+        let comparison = Comparison {
+            k,
+            a: args.input[0].display().to_string(),
+            b: args.input[1].display().to_string(),
+            solid_a: a.unique_solid_kmers(),
+            solid_b: b.unique_solid_kmers(),
+            intersection,
+            union,
+            jaccard,
+            interrupted: stop.ends_run(),
+        };
+        println!("{}", serde_json::to_string(&comparison)?);
+    } else {
+        println!(
+            "Solid k-mers: {} shared, {} in the union. Jaccard index: {:.4}",
+            args.count(intersection), args.count(union), jaccard
+        );
+    }
+    Ok(())
+}
+
+/// --matrix: sketch each input in one pass, then compare every pair of sketches
+async fn distance_matrix(
+    args: &Args,
+    path: &Path,
+    threads: usize,
+    reporter: &mut Reporter<'_>,
+    control: &Control,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut sketches = Vec::with_capacity(args.input.len());
+    let (mut reason, mut reads) = ("end_of_input", 0);
+    for input in &args.input {
+        // Only the sketch is kept, so one k-mer set is in memory at a time
+        let (counter, run) = count_input(args, input, threads, reporter, control).await?;
+        sketches.push(counter.minhash().expect("--matrix keeps a MinHash sketch").clone());
+        reads += run.reads;
+        if run.stop.ends_run() || run.stop == Stop::MemoryCapped {
+            note!(args, "The matrix only covers the inputs read before reading stopped.");
+            reason = run.stop.name();
+            break;
+        }
+    }
+    reporter.finish(reason, reads).await;
+
+    let n = sketches.len();
+    let rows: Vec<Vec<f64>> = (0..n)
+        .into_par_iter()
+        .map(|i| (i + 1..n).map(|j| 1.0 - sketches[i].jaccard(&sketches[j])).collect())
+        .collect();
+    let distance = |i: usize, j: usize| match i.cmp(&j) {
+        std::cmp::Ordering::Less => rows[i][j - i - 1],
+        std::cmp::Ordering::Equal => 0.0,
+        std::cmp::Ordering::Greater => rows[j][i - j - 1],
+    };
+
+    let names: Vec<String> = args.input[..n].iter().map(|input| input.display().to_string()).collect();
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "\t{}", names.join("\t"))?;
+    for (i, name) in names.iter().enumerate() {
+        write!(out, "{}", name)?;
+        for j in 0..n {
+            write!(out, "\t{:.6}", distance(i, j))?;
+        }
+        writeln!(out)?;
+    }
+    out.flush()?;
+    note!(args, "Wrote the {} x {} Jaccard distance matrix to {}", n, n, path.display());
+    Ok(())
+}
+
+/// --two-pass: read the same reads once into HyperLogLog sketches, estimating each
+/// k's distinct k-mers in the whole input; None if interrupted or out of time
+async fn estimate_distinct(
+    args: &Args,
+    threads: usize,
+    control: &Control,
+) -> Result<Option<Vec<u64>>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut first = args.clone();
+    first.estimate = true;
+    // Used wherever the settings allow it
+    first.nthash = true;
+    first.no_auto_stop = true;
+    first.max_memory_mb = None;
+    first.seq_stats = false;
+    first.sketch_out = None;
+    let mut tracks: Vec<Track> = first.ks().iter().map(|&k| Track::new(build_counter(&first, k, threads))).collect();
+    let mut reporter = Reporter {
+        args: &first,
+        progress_log: None,
+        live: None,
+        started: Instant::now(),
+        last_sample: (Instant::now(), 0, 0),
+        quiet: true,
+    };
+    note!(args, "Estimating the distinct k-mers of the input...");
+    let run = count_reads(&first, &first.input, None, &mut tracks, &mut reporter, control).await?;
+    if run.stop.ends_run() {
+        return Ok(None);
+    }
+    Ok(Some(tracks.iter().map(|track| track.counter.distinct_kmers()).collect()))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut args = Args::parse();
+    // R1 stands for the pair wherever inputs are opened
+    if let Some(first) = args.input_1.clone() {
+        args.input = vec![first];
+    }
+    // Logs go to stderr, next to the notes of --json runs
+    let filter = match &args.log_level {
+        Some(directives) => EnvFilter::new(directives),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("off")),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .init();
+    if args.jaccard && args.input.len() != 2 {
+        Args::command()
+            .error(ErrorKind::WrongNumberOfValues, "--jaccard compares exactly two --input files")
+            .exit();
+    }
+    if args.matrix.is_some() && args.input.len() < 2 {
+        Args::command()
+            .error(ErrorKind::TooFewValues, "--matrix compares at least two --input files")
+            .exit();
+    }
+    if let Some(width) = args.count_width.filter(|width| args.min_count > width.max_count()) {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!("--min-count {} cannot be reached by {}-bit counts", args.min_count, width.bits()),
+            )
+            .exit();
+    }
+    if let Some((min, max)) = args.gc_range().filter(|&(min, max)| min > max) {
+        Args::command()
+            .error(ErrorKind::ArgumentConflict, format!("--min-gc {} is above --max-gc {}", min, max))
+            .exit();
+    }
+    if args.sorted.is_some() && args.dump_kmers.is_none() && args.dump_fasta.is_none() {
+        Args::command()
+            .error(ErrorKind::MissingRequiredArgument, "--sorted orders the output of --dump-kmers or --dump-fasta")
+            .exit();
+    }
+    // Standard input and pipes cannot be read a second time
+    let rereadable = |path: &&PathBuf| std::fs::metadata(path).is_ok_and(|m| m.is_file());
+    if let Some(path) = args.input_files().find(|path| args.two_pass && !rereadable(path)) {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!("--two-pass reads the input twice, but {} is not a regular file", path.display()),
+            )
+            .exit();
+    }
+
+    if args.validate {
+        if !validate_inputs(&args)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let threads = args
+        .threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1));
+    rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()?;
+
+    let ks = args.ks();
+    info!(inputs = ?args.input_files().collect::<Vec<_>>(), ?ks, threads, "starting");
+    let mut tracks: Vec<Track> = ks.iter().map(|&k| Track::new(build_counter(&args, k, threads))).collect();
+
+    // The first Ctrl-C stops reading so the results so far are still reported
+    // and written; a second one exits immediately
+    let control = Arc::new(Control::new(args.max_time));
+    {
+        let control = control.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                control.interrupt();
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    std::process::exit(130);
+                }
+            }
+        });
+    }
+
+    let live = if args.no_ws {
+        None
+    } else {
+        let (updates, _) = broadcast::channel(WS_BUFFER);
+        let latest = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        // WebSocket route
+        let clients = updates.clone();
+        let commands = control.clone();
+        let token: Option<Arc<str>> = args.ws_token.as_deref().map(Arc::from);
+        let ws_route = warp::path("ws")
+            .and(authorized(token.clone()))
+            .and(warp::ws())
+            .and(warp::addr::remote())
+            .map(move |ws: warp::ws::Ws, peer: Option<SocketAddr>| {
+                let rx = clients.subscribe();
+                let control = commands.clone();
+                let span = info_span!("ws_client", peer = %peer.map_or_else(|| "unknown".to_string(), |peer| peer.to_string()));
+                ws.on_upgrade(move |socket| handle_connection(socket, rx, control).instrument(span))
+            });
+
+        // Live dashboard, the same page as plot.html
+        let dashboard_route = warp::path::end().map(|| warp::reply::html(DASHBOARD));
+
+        // Latest snapshot on demand, for polling clients and health checks: one
+        // object, or one per k with --k-range
+        let stats = latest.clone();
+        let sweep = ks.len() > 1;
+        let stats_route = warp::path("stats")
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(authorized(token.clone()))
+            .map(move || {
+                let latest = stats.lock().unwrap();
+                match latest.first() {
+                    Some(snapshot) if !sweep => warp::reply::json(snapshot),
+                    None if !sweep => warp::reply::json(&Snapshot::default()),
+                    _ => warp::reply::json(&*latest),
+                }
+            });
+
+        // The same snapshots for Prometheus scrapers
+        let metrics = latest.clone();
+        let metrics_route = warp::path("metrics")
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(authorized(token))
+            .map(move || {
+                let body = prometheus_metrics(&metrics.lock().unwrap());
+                warp::reply::with_header(body, "content-type", "text/plain; version=0.0.4")
+            });
+
+        let addr = SocketAddr::new(args.ws_host, args.ws_port);
+        let routes = dashboard_route.or(ws_route).or(stats_route).or(metrics_route);
+        let server = warp::serve(routes.recover(reject_unauthorized));
+        let tls = args.tls_cert.clone().zip(args.tls_key.clone());
+        info!(%addr, tls = tls.is_some(), "starting the web server");
+        tokio::spawn(async move {
+            match tls {
+                Some((cert, key)) => server.tls().cert_path(cert).key_path(key).run(addr).await,
+                None => server.run(addr).await,
+            }
+        });
+        Some(Live::new(updates, latest, args.ws_max_rate))
+    };
+
+    if let Some(path) = &args.load {
+        // --load conflicts with --k-range, so there is a single counter
+        let counter = &mut tracks[0].counter;
+        let mut input = std::io::BufReader::new(File::open(path)?);
+        counter
+            .load(&mut input)
+            .map_err(|e| format!("failed to load {}: {}", path.display(), e))?;
+        note!(
+            args,
+            "Loaded {} k-mers ({} solid) from {}",
+            args.count(counter.distinct_kmers()),
+            args.count(counter.unique_solid_kmers()),
+            path.display()
+        );
+    }
+
+    let mut reporter = Reporter {
+        args: &args,
+        progress_log: args.progress_log.as_deref().map(open_progress_log).transpose()?,
+        live,
+        started: Instant::now(),
+        last_sample: (Instant::now(), 0, 0),
+        quiet: false,
+    };
+
+    if args.jaccard {
+        return compare_inputs(&args, threads, &mut reporter, &control).await;
+    }
+    if let Some(path) = &args.matrix {
+        return distance_matrix(&args, path, threads, &mut reporter, &control).await;
+    }
+
+    // Prefix naming the k in per-k messages of a sweep
+    let sweep = tracks.len() > 1;
+    let k_label = |counter: &KmerCounter| if sweep { format!("k={}: ", counter.k()) } else { String::new() };
+
+    if args.two_pass {
+        let Some(totals) = estimate_distinct(&args, threads, &control).await? else {
+            note!(args, "Stopped during the estimate pass.");
+            return Ok(());
+        };
+        for (track, total) in tracks.iter_mut().zip(totals) {
+            note!(
+                args,
+                "{}Estimated {} distinct k-mers in the input; counting them exactly.",
+                k_label(&track.counter),
+                args.count(total)
+            );
+            track.coverage = Some(Coverage::new(total));
+        }
+    }
+
+    let mut sampled_malformed = Malformed::default();
+    let reservoir =
+        args.reservoir.map(|size| reservoir_sample(&args, size, &mut sampled_malformed, &control)).transpose()?;
+
+    // Growth is measured from the loaded snapshot, if any
+    for track in &mut tracks {
+        track.restart(&args);
+    }
+    let run = count_reads(&args, &args.input, reservoir, &mut tracks, &mut reporter, &control).await?;
+    reporter.finish(run.stop.name(), run.reads).await;
+    let Run {
+        reads: idx,
+        bases,
+        short_reads,
+        adapter_bases,
+        duplicate_reads,
+        malformed_records,
+        pairs,
+        stop,
+        last_file,
+        seq_stats,
+        non_acgt,
+        per_file,
+    } = run;
+    let malformed_records = malformed_records + sampled_malformed.skipped;
+    let current = last_file.as_path();
+    let min_len = args.min_len();
+
+    for Track { counter, .. } in &tracks {
+        if let Some(error) = counter.estimate_error() {
+            note!(
+                args,
+                "{}Estimated distinct k-mers: {} (relative error ±{:.2}%)",
+                k_label(counter),
+                args.count(counter.distinct_kmers()),
+                error * 100.0
+            );
+        }
+    }
+
+    let projections: Vec<Option<Option<Projection>>> =
+        tracks.iter().map(|track| args.target_saturation.map(|target| track.project(target, idx))).collect();
+    for (Track { counter, .. }, projection) in tracks.iter().zip(&projections) {
+        match projection {
+            Some(Some(projection)) if projection.more_reads > 0 => note!(
+                args,
+                "{}Saturation model: ~{} k-mers at most, {:.1}% reached; {}% needs ~{} reads ({} more).",
+                k_label(counter),
+                args.count(projection.asymptote.round() as u64),
+                projection.saturation * 100.0,
+                projection.target * 100.0,
+                args.count(projection.target_reads),
+                args.count(projection.more_reads)
+            ),
+            Some(Some(projection)) => note!(
+                args,
+                "{}Saturation model: ~{} k-mers at most, {:.1}% reached; {}% was reached after ~{} reads.",
+                k_label(counter),
+                args.count(projection.asymptote.round() as u64),
+                projection.saturation * 100.0,
+                projection.target * 100.0,
+                args.count(projection.target_reads)
+            ),
+            Some(None) => note!(
+                args,
+                "{}No saturation curve fits: too few samples, or the k-mers still grow too steadily to extrapolate.",
+                k_label(counter)
+            ),
+            None => {}
+        }
+    }
+
+    if let Some(path) = &args.curve_out {
+        let curves: Vec<CurveFile> =
+            tracks.iter().map(|track| CurveFile { k: track.counter.k(), samples: &track.curve }).collect();
+        let mut out = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut out, &curves)?;
+        writeln!(out)?;
+        out.flush()?;
+        note!(args, "Wrote {} samples of the k-mer curve to {}", tracks[0].curve.len(), path.display());
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(path) = &args.parquet {
+        write_parquet(path, &tracks)?;
+        note!(args, "Wrote {} samples of the k-mer curve as Parquet to {}", tracks[0].curve.len(), path.display());
+    }
+
+    // The remaining single-k outputs conflict with --k-range
+    let counter = &tracks[0].counter;
+
+    if let Some(path) = &args.histogram {
+        write_histogram(path, &counter.histogram())?;
+        note!(args, "Wrote k-mer histogram to {}", path.display());
+    }
+    if let Some(path) = &args.spectrum_svg {
+        std::fs::write(path, spectrum_svg(&counter.histogram(), counter.k()))?;
+        note!(args, "Wrote the k-mer spectrum chart to {}", path.display());
+    }
+
+    let genome = args.estimate_genome_size.then(|| {
+        let mean_read_length = if idx > 0 { bases as f64 / idx as f64 } else { 0.0 };
+        estimate_genome_size(&counter.histogram()).map(|estimate| GenomeReport::new(&estimate, counter.k(), mean_read_length))
+    });
+    match genome {
+        Some(Some(genome)) => {
+            let depth = genome.base_coverage.map(|depth| format!(", base depth ~{:.1}x", depth)).unwrap_or_default();
+            note!(
+                args,
+                "Coverage peak at {}x (errors below {}x){}: estimated genome size {} bp",
+                genome.peak_coverage, genome.error_cutoff, depth, args.count(genome.genome_size)
+            );
+        }
+        Some(None) => note!(args, "Genome size estimation failed: the k-mer spectrum has no clear coverage peak."),
+        None => {}
+    }
+
+    if let Some(path) = &args.dump_kmers {
+        dump_kmers(path, counter, false, args.sorted)?;
+        note!(args, "Wrote solid k-mers to {}", path.display());
+    }
+    if let Some(path) = &args.dump_fasta {
+        dump_kmers(path, counter, true, args.sorted)?;
+        note!(args, "Wrote solid k-mers as FASTA to {}", path.display());
+    }
+
+    let top_kmers = args.top_n.map(|n| counter.top_kmers(n));
+    if let Some(top) = &top_kmers {
+        note!(args, "Most frequent k-mers:");
+        for (kmer, count) in top {
+            note!(args, "  {}\t{}", String::from_utf8_lossy(kmer), count);
+        }
+    }
+
+    if let Some(path) = &args.sketch_out {
+        let minhash = counter.minhash().expect("--sketch-out keeps a MinHash sketch");
+        let sketch = SketchFile {
+            hash_function: "murmur3_x64_128",
+            seed: counter.hash_seed(),
+            ksize: counter.k(),
+            num: minhash.size(),
+            molecule: if counter.is_protein() {
+                "protein"
+            } else if counter.is_rna() {
+                "RNA"
+            } else {
+                "DNA"
+            },
+            canonical: !counter.is_forward_only(),
+            seed_pattern: args.seed_pattern.as_deref(),
+            minimizer: args.minimizer,
+            filenames: args.input_files().map(|path| path.display().to_string()).collect(),
+            mins: minhash.hashes().collect(),
+        };
+        let mut out = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut out, &sketch)?;
+        writeln!(out)?;
+        out.flush()?;
+        note!(args, "Wrote a MinHash sketch of {} hashes to {}", sketch.mins.len(), path.display());
+    }
+
+    if let Some(path) = &args.save {
+        let mut out = BufWriter::new(File::create(path)?);
+        counter.save(&mut out)?;
+        out.flush()?;
+        note!(args, "Saved {} k-mers to {}", args.count(counter.distinct_kmers()), path.display());
+    }
+
+    for Track { counter, .. } in &tracks {
+        let Some(sketch) = counter.count_min() else {
+            continue;
+        };
+        note!(
+            args,
+            "{}Count-Min sketch: {} x {} counters ({:.1} MB). Solid k-mers: {} (upper estimate).",
+            k_label(counter),
+            sketch.depth(),
+            sketch.width(),
+            sketch.memory_bytes() as f64 / (1 << 20) as f64,
+            args.count(counter.unique_solid_kmers())
+        );
+        note!(
+            args,
+            "Collisions only inflate counts: each k-mer's count is over-estimated by at most {:.1} \
+             with {:.1}% confidence, so some singletons may be reported as solid.",
+            sketch.error_bound(),
+            sketch.confidence() * 100.0
+        );
+        note!(args, "Most frequent k-mers (estimated counts):");
+        for hitter in counter.heavy_hitters() {
+            note!(args, "  {}\t~{}", String::from_utf8_lossy(&hitter.kmer), hitter.count);
+        }
+    }
+
+    if args.json {
+        for (index, (Track { counter, tracker, coverage, .. }, projection)) in tracks.iter().zip(projections).enumerate() {
+            let summary = Summary {
+                progress: Progress {
+                    file: current.display().to_string(),
+                    k: counter.k(),
+                    reads: idx,
+                    distinct_kmers: counter.distinct_kmers(),
+                    solid_kmers: counter.unique_solid_kmers(),
+                    total_kmers: counter.total_kmers(),
+                    avg_growth: tracker.last().avg_growth,
+                    avg_accel: tracker.last().avg_accel,
+                    throughput: reporter.overall(idx, bases),
+                    map_bytes: args.report_memory.then(|| counter.memory_bytes() as u64),
+                    count_bits: counter.count_width().filter(|_| args.report_memory).map(CountWidth::bits),
+                    rss_bytes: if args.report_memory { resident_memory() } else { None },
+                    estimated_total: coverage.as_ref().map(|coverage| coverage.total),
+                    saturation_pct: coverage.as_ref().map(Coverage::percent),
+                    redundant_pct: counter.redundancy().map(|(reads, redundant)| percent(redundant, reads)),
+                },
+                stopped_early: stop == Stop::Saturated,
+                interrupted: stop == Stop::Interrupted,
+                memory_capped: stop == Stop::MemoryCapped,
+                time_limited: stop == Stop::TimeLimit,
+                stop_reason: stop.name(),
+                skipped_kmers: counter.skipped_kmers(),
+                low_complexity_kmers: args.mask_low_complexity.then(|| counter.low_complexity_kmers()),
+                palindromic_kmers: args.count_palindromes.then(|| counter.palindromic_solid_kmers()),
+                homopolymer_kmers: args.max_homopolymer.map(|_| counter.homopolymer_kmers()),
+                gc_kmers: args.gc_range().map(|_| counter.gc_kmers()),
+                strands: counter.strand_counts().map(StrandReport::from),
+                pairs,
+                short_reads,
+                adapter_bases: args.adapters.is_some().then_some(adapter_bases),
+                duplicate_reads,
+                non_acgt_reads: non_acgt.as_ref().map(NonAcgt::reads),
+                non_acgt_bases: non_acgt.as_ref().map(|tally| {
+                    let counts = tally.counts().into_iter();
+                    counts.map(|(byte, count)| ByteCount { byte: byte_label(byte), count }).collect()
+                }),
+                malformed_records: args.skip_errors.then_some(malformed_records),
+                distinct_error: counter.estimate_error(),
+                seq_stats: seq_stats.as_ref().map(SeqStatsReport::from),
+                per_file: per_file.as_ref().map(|files| {
+                    files
+                        .iter()
+                        .map(|file| FileReport {
+                            file: file.file.display().to_string(),
+                            reads: file.reads,
+                            bases: file.bases,
+                            new_distinct_kmers: file.new_distinct[index],
+                            new_solid_kmers: file.new_solid[index],
+                        })
+                        .collect()
+                }),
+                genome_estimate: genome,
+                top_kmers: top_kmers.as_ref().map(|top| {
+                    top.iter()
+                        .map(|(kmer, count)| TopKmer { kmer: String::from_utf8_lossy(kmer).into_owned(), count: *count })
+                        .collect()
+                }),
+                projection,
+            };
+            println!("{}", serde_json::to_string(&summary)?);
+        }
+    } else {
+        let pair_note = pairs.map(|pairs| format!("{} pairs, ", args.count(pairs))).unwrap_or_default();
+        println!(
+            "Processed {} reads ({}{} bases) in {:.1} s: {}",
+            args.count(idx),
+            pair_note,
+            args.count(bases),
+            reporter.started.elapsed().as_secs_f64(),
+            reporter.overall(idx, bases)
+        );
+        if let Some(stats) = &seq_stats {
+            print_seq_stats(&args, stats);
+        }
+        if let Some(files) = &per_file {
+            print_per_file(&args, &tracks, files);
+        }
+        for Track { counter, .. } in &tracks {
+            let total = counter.total_kmers();
+            println!(
+                "{}Counted {} k-mers, {} distinct ({:.2}% of the total).",
+                k_label(counter),
+                args.count(total),
+                args.count(counter.distinct_kmers()),
+                percent(counter.distinct_kmers(), total)
+            );
+        }
+        if !args.allow_ambiguous {
+            for Track { counter, .. } in &tracks {
+                println!(
+                    "{}Skipped {} k-mers containing {}.",
+                    k_label(counter),
+                    args.count(counter.skipped_kmers()),
+                    if args.protein { "non-standard residues" } else { "ambiguous bases" }
+                );
+            }
+        }
+        if args.mask_low_complexity {
+            for Track { counter, .. } in &tracks {
+                println!(
+                    "{}Masked {} low-complexity k-mers (dinucleotide entropy under {} bits).",
+                    k_label(counter),
+                    args.count(counter.low_complexity_kmers()),
+                    args.complexity_threshold
+                );
+            }
+        }
+        if let Some(max) = args.max_homopolymer {
+            for Track { counter, .. } in &tracks {
+                println!(
+                    "{}Skipped {} k-mers with a homopolymer longer than {} bases.",
+                    k_label(counter),
+                    args.count(counter.homopolymer_kmers()),
+                    max
+                );
+            }
+        }
+        if let Some((min, max)) = args.gc_range() {
+            for Track { counter, .. } in &tracks {
+                println!(
+                    "{}Skipped {} k-mers with a GC fraction outside {} to {}.",
+                    k_label(counter),
+                    args.count(counter.gc_kmers()),
+                    min,
+                    max
+                );
+            }
+        }
+        if args.count_palindromes {
+            for Track { counter, .. } in &tracks {
+                let palindromes = counter.palindromic_solid_kmers();
+                println!(
+                    "{}{} of {} solid k-mers ({:.2}%) are reverse-complement palindromes.",
+                    k_label(counter),
+                    args.count(palindromes),
+                    args.count(counter.unique_solid_kmers()),
+                    percent(palindromes, counter.unique_solid_kmers())
+                );
+            }
+        }
+        for Track { counter, .. } in &tracks {
+            if let Some(strands) = counter.strand_counts() {
+                println!(
+                    "{}Strands: {} k-mers read forward, {} as reverse complements ({:.1}% forward), {} palindromic.",
+                    k_label(counter),
+                    args.count(strands.forward),
+                    args.count(strands.reverse),
+                    strands.forward_fraction() * 100.0,
+                    args.count(strands.palindromic)
+                );
+            }
+        }
+        for Track { counter, .. } in &tracks {
+            if let Some((reads, redundant)) = counter.redundancy() {
+                println!(
+                    "{}{} of {} reads ({:.1}%) were redundant: all their k-mers were already solid.",
+                    k_label(counter),
+                    args.count(redundant),
+                    args.count(reads),
+                    percent(redundant, reads)
+                );
+            }
+        }
+        println!("Skipped {} reads shorter than {} bases.", args.count(short_reads), min_len);
+        if let Some(duplicates) = duplicate_reads {
+            println!(
+                "Removed {} duplicate reads ({:.2}% of the reads before deduplication), counting one copy of each.",
+                args.count(duplicates),
+                percent(duplicates, idx + duplicates)
+            );
+        }
+        if args.adapters.is_some() {
+            let masked = args.count(adapter_bases);
+            println!("Masked {} adapter bases ({:.2}% of the bases read).", masked, percent(adapter_bases, bases));
+        }
+        if args.skip_errors {
+            println!("Skipped {} malformed records.", args.count(malformed_records));
+        }
+        if let Some(tally) = non_acgt.as_ref().filter(|tally| tally.reads() > 0) {
+            print_non_acgt(&args, tally);
+        }
     }
 
     Ok(())
@@ -0,0 +1,76 @@
+//! ntHash rolling hash of DNA k-mers (Mohamadi et al., Bioinformatics 2016):
+//! sliding the window by one base updates both strand hashes in constant time.
+
+/// Per-base seeds of the original ntHash
+const SEED_A: u64 = 0x3c8b_fbb3_95c6_0474;
+const SEED_C: u64 = 0x3193_c185_62a0_2b4c;
+const SEED_G: u64 = 0x2032_3ed0_8257_2324;
+const SEED_T: u64 = 0x2955_49f5_4be2_4456;
+
+/// Seeds indexed by base byte (U hashes as T); other bytes get 0 and must not be hashed
+const SEEDS: [u64; 256] = seed_table(false);
+/// Seeds of the complement of each base
+const COMPLEMENT_SEEDS: [u64; 256] = seed_table(true);
+
+const fn seed_table(complement: bool) -> [u64; 256] {
+    let (a, c, g, t) = if complement { (SEED_T, SEED_G, SEED_C, SEED_A) } else { (SEED_A, SEED_C, SEED_G, SEED_T) };
+    let mut table = [0; 256];
+    table[b'A' as usize] = a;
+    table[b'a' as usize] = a;
+    table[b'C' as usize] = c;
+    table[b'c' as usize] = c;
+    table[b'G' as usize] = g;
+    table[b'g' as usize] = g;
+    table[b'T' as usize] = t;
+    table[b't' as usize] = t;
+    table[b'U' as usize] = t;
+    table[b'u' as usize] = t;
+    table
+}
+
+fn seed(base: u8) -> u64 {
+    SEEDS[base as usize]
+}
+
+fn complement_seed(base: u8) -> u64 {
+    COMPLEMENT_SEEDS[base as usize]
+}
+
+/// Hashes of the current window on both strands. Only meaningful for
+/// windows of unambiguous bases.
+#[derive(Clone, Copy, Debug)]
+pub struct NtHash {
+    k: u32,
+    forward: u64,
+    reverse: u64,
+}
+
+impl NtHash {
+    /// Hash the first window, `kmer`
+    pub fn new(kmer: &[u8]) -> Self {
+        let k = kmer.len() as u32;
+        let mut forward = 0;
+        let mut reverse = 0;
+        for (i, &base) in kmer.iter().enumerate() {
+            forward ^= seed(base).rotate_left(k - 1 - i as u32);
+            reverse ^= complement_seed(base).rotate_left(i as u32);
+        }
+        NtHash { k, forward, reverse }
+    }
+
+    /// Slide the window one base: `out` leaves at the front, `next` enters at the back
+    pub fn roll(&mut self, out: u8, next: u8) {
+        self.forward = self.forward.rotate_left(1) ^ seed(out).rotate_left(self.k) ^ seed(next);
+        self.reverse = (self.reverse ^ complement_seed(out)).rotate_right(1) ^ complement_seed(next).rotate_left(self.k - 1);
+    }
+
+    /// Hash of the window as read
+    pub fn forward(&self) -> u64 {
+        self.forward
+    }
+
+    /// Strand-independent hash: the same for a k-mer and its reverse complement
+    pub fn canonical(&self) -> u64 {
+        self.forward.min(self.reverse)
+    }
+}
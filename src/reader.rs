@@ -0,0 +1,506 @@
+use bio::io::fasta::{self, FastaRead};
+use bio::io::fastq::{self, FastqRead};
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+mod mapped;
+
+use mapped::MappedRecords;
+
+/// Offset of Phred quality scores in FASTQ quality strings (Sanger / Illumina 1.8+)
+pub const PHRED_OFFSET: u8 = 33;
+
+/// Offset of the older Phred+64 encoding (Illumina 1.3 to 1.7)
+pub const PHRED64_OFFSET: u8 = 64;
+
+/// Highest byte a FASTQ quality string may hold (`~`)
+const MAX_QUALITY_BYTE: u8 = b'~';
+
+/// One input record
+pub struct SeqRecord {
+    pub seq: Vec<u8>,
+    /// Phred+33 quality string, for FASTQ records
+    pub qual: Option<Vec<u8>>,
+}
+
+impl SeqRecord {
+    /// Trim both ends of a FASTQ record with a sliding window: the read starts
+    /// at the first window and ends with the last window whose mean Phred
+    /// quality is at least `min_qual`. Reads shorter than `window` are judged
+    /// by their overall mean. Records without qualities are left untouched.
+    pub fn trim(&mut self, min_qual: u8, window: usize) {
+        let Some(qual) = &mut self.qual else {
+            return;
+        };
+        let len = qual.len().min(self.seq.len());
+        let window = window.clamp(1, len.max(1));
+        let scores: Vec<u32> = qual[..len].iter().map(|&q| q.saturating_sub(PHRED_OFFSET) as u32).collect();
+        let passes = |w: &[u32]| w.iter().sum::<u32>() >= min_qual as u32 * w.len() as u32;
+
+        let (start, end) = match scores.windows(window).position(passes) {
+            Some(first) => {
+                let last = scores.windows(window).rposition(passes).unwrap_or(first);
+                (first, last + window)
+            }
+            None => (0, 0),
+        };
+        self.seq.truncate(end);
+        self.seq.drain(..start);
+        qual.truncate(end);
+        qual.drain(..start);
+    }
+
+    /// Maximal stretches of the sequence whose bases all have a Phred quality
+    /// of at least `min_qual`. Records without qualities are returned whole.
+    pub fn quality_runs(&self, min_qual: u8) -> Vec<&[u8]> {
+        self.quality_ranges(min_qual).into_iter().map(|range| &self.seq[range]).collect()
+    }
+
+    /// Positions of the [`quality_runs`](Self::quality_runs) in the sequence
+    pub fn quality_ranges(&self, min_qual: u8) -> Vec<Range<usize>> {
+        let Some(qual) = &self.qual else {
+            let whole = 0..self.seq.len();
+            return vec![whole];
+        };
+        let threshold = PHRED_OFFSET.saturating_add(min_qual);
+        let mut runs = Vec::new();
+        let mut start = 0;
+        for (i, &q) in qual.iter().enumerate().take(self.seq.len()) {
+            if q < threshold {
+                if i > start {
+                    runs.push(start..i);
+                }
+                start = i + 1;
+            }
+        }
+        if self.seq.len() > start {
+            runs.push(start..self.seq.len());
+        }
+        runs
+    }
+
+    /// Convert the quality string from Phred+`offset` to Phred+33, failing on the first
+    /// byte that encoding cannot hold. Records without qualities are left untouched.
+    pub fn rebase_quality(&mut self, offset: u8) -> Result<(), String> {
+        let Some(qual) = &mut self.qual else {
+            return Ok(());
+        };
+        if let Some(&q) = qual.iter().find(|&&q| q < offset || q > MAX_QUALITY_BYTE) {
+            return Err(format!(
+                "quality {:?} (ASCII {}) is outside the Phred+{} range (ASCII {} to {})",
+                q as char, q, offset, offset, MAX_QUALITY_BYTE
+            ));
+        }
+        if offset != PHRED_OFFSET {
+            for q in qual.iter_mut() {
+                *q = *q - offset + PHRED_OFFSET;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A record that failed to parse, with where it is in which input
+pub struct RecordError {
+    pub path: PathBuf,
+    /// 1-based position of the record in its input
+    pub record: u64,
+    pub source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl RecordError {
+    pub fn new(path: &Path, record: u64, source: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        RecordError { path: path.to_path_buf(), record, source }
+    }
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error parsing record {} of {}: {}", self.record, self.path.display(), self.source)?;
+        // Parser errors such as "can't read input" keep the actual cause underneath
+        let mut cause = self.source.source();
+        while let Some(error) = cause {
+            write!(f, ": {}", error)?;
+            cause = error.source();
+        }
+        Ok(())
+    }
+}
+
+// The message, as `main` returning this error prints its Debug form
+impl fmt::Debug for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for RecordError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Sequence records from either a FASTA or a FASTQ stream. Each parser reads into a
+/// record of its own, reused from one record to the next.
+pub enum RecordReader<R: Read> {
+    Fasta {
+        reader: fasta::Reader<BufReader<R>>,
+        record: fasta::Record,
+        /// A FASTA parse error leaves the parser stuck on the bad line, so reading ends there
+        failed: bool,
+    },
+    Fastq {
+        reader: fastq::Reader<BufReader<R>>,
+        record: fastq::Record,
+    },
+    /// Either format, parsed from a memory-mapped file (see [`open_mapped`])
+    Mapped(MappedRecords),
+}
+
+impl<R: Read> RecordReader<R> {
+    fn fasta(reader: BufReader<R>) -> Self {
+        RecordReader::Fasta { reader: fasta::Reader::from_bufread(reader), record: fasta::Record::new(), failed: false }
+    }
+
+    fn fastq(reader: BufReader<R>) -> Self {
+        RecordReader::Fastq { reader: fastq::Reader::from_bufread(reader), record: fastq::Record::new() }
+    }
+
+    /// Next record, with its sequence uppercased unless `case_sensitive` is set
+    pub fn next_record(&mut self, case_sensitive: bool) -> Option<Result<SeqRecord, Box<dyn std::error::Error + Send + Sync>>> {
+        let mut record = SeqRecord { seq: Vec::new(), qual: None };
+        self.read_into(&mut record, case_sensitive).map(|read| read.map(|()| record))
+    }
+
+    /// [`next_record`](Self::next_record) into `record`, whose sequence and quality
+    /// buffers are cleared and refilled, so a loop reusing one record allocates nothing
+    /// once they have grown to the longest read. Qualities are None for FASTA; a FASTQ
+    /// record whose quality line is not as long as its sequence is an error. Once this
+    /// returns None, at the end of the input, it keeps returning None.
+    pub fn read_into(
+        &mut self,
+        record: &mut SeqRecord,
+        case_sensitive: bool,
+    ) -> Option<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
+        let read = match self {
+            RecordReader::Fasta { failed: true, .. } => return None,
+            RecordReader::Fasta { reader, record: parsed, failed } => match reader.read(parsed) {
+                Ok(()) if parsed.is_empty() => return None,
+                Ok(()) => {
+                    record.seq.clear();
+                    record.seq.extend_from_slice(parsed.seq());
+                    record.qual = None;
+                    Ok(())
+                }
+                Err(e) => {
+                    *failed = true;
+                    Err(e.into())
+                }
+            },
+            RecordReader::Fastq { reader, record: parsed } => match reader.read(parsed) {
+                Ok(()) if parsed.is_empty() => return None,
+                Ok(()) => {
+                    record.seq.clear();
+                    record.seq.extend_from_slice(parsed.seq());
+                    let qual = record.qual.get_or_insert_with(Vec::new);
+                    qual.clear();
+                    qual.extend_from_slice(parsed.qual());
+                    Ok(())
+                }
+                Err(e) => Err(e.into()),
+            },
+            RecordReader::Mapped(records) => records.read_into(record)?,
+        };
+        // Neither parser checks this, and every quality filter pairs bases with their scores
+        let read = read.and_then(|()| match &record.qual {
+            Some(qual) if qual.len() != record.seq.len() => Err(format!(
+                "the sequence line holds {} bases but the quality line {} scores",
+                record.seq.len(),
+                qual.len()
+            )
+            .into()),
+            _ => Ok(()),
+        });
+        if read.is_ok() && !case_sensitive {
+            record.seq.make_ascii_uppercase();
+        }
+        Some(read)
+    }
+}
+
+/// Record reader over a (possibly decompressed) input file or standard input
+pub type FileRecordReader = RecordReader<Box<dyn Read>>;
+
+/// Read buffer of [`open_reader`], far larger than `BufReader`'s 8 KiB to cut the read calls on large files
+pub const DEFAULT_BUFFER_SIZE: usize = 1 << 20;
+
+/// Path that selects standard input instead of a file
+pub const STDIN_PATH: &str = "-";
+
+/// Raw, still possibly compressed, input stream
+type RawInput = BufReader<Box<dyn Read>>;
+
+/// A compression format recognized on input
+struct Codec {
+    /// Bytes every stream in this format starts with
+    magic: &'static [u8],
+    /// Extension used to decide when the stream is too short to hold `magic`
+    extension: &'static str,
+    /// Wrap the raw stream in a decoder
+    decode: fn(RawInput) -> std::io::Result<Box<dyn Read>>,
+}
+
+/// Supported input compressions, tried in order. Multi-member/multi-frame
+/// streams (e.g. concatenated `.gz` files) are decoded to the end.
+const CODECS: &[Codec] = &[
+    Codec { magic: &[0x1f, 0x8b], extension: "gz", decode: |r| Ok(Box::new(MultiGzDecoder::new(r))) },
+    Codec { magic: &[0x28, 0xb5, 0x2f, 0xfd], extension: "zst", decode: |r| Ok(Box::new(zstd::Decoder::with_buffer(r)?)) },
+    Codec { magic: b"BZh", extension: "bz2", decode: |r| Ok(Box::new(MultiBzDecoder::new(r))) },
+];
+
+impl Codec {
+    /// Whether the stream starts with this codec's magic; when it is too
+    /// short to tell, fall back to the file extension
+    fn matches(&self, head: &[u8], path: &Path) -> bool {
+        if head.len() >= self.magic.len() {
+            head.starts_with(self.magic)
+        } else {
+            path.extension().map(|e| e == self.extension).unwrap_or(false)
+        }
+    }
+}
+
+/// Open a FASTA/FASTQ file, or standard input when `path` is `-`. The
+/// compression is detected from the magic bytes (see `CODECS`) and the
+/// format from the first decompressed byte.
+pub fn open_reader(path: &Path) -> Result<FileRecordReader, Box<dyn std::error::Error + Send + Sync>> {
+    open_reader_with_capacity(path, DEFAULT_BUFFER_SIZE)
+}
+
+/// [`open_reader`] with read buffers of `capacity` bytes, for the file and, when it is
+/// compressed, for the decompressed stream
+pub fn open_reader_with_capacity(path: &Path, capacity: usize) -> Result<FileRecordReader, Box<dyn std::error::Error + Send + Sync>> {
+    let source: Box<dyn Read> = if path == Path::new(STDIN_PATH) {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(File::open(path)?)
+    };
+    let mut raw: RawInput = BufReader::with_capacity(capacity, source);
+
+    // Decide compression by magic bytes; the extension only matters when the
+    // stream is too short to hold them
+    let head = raw.fill_buf()?;
+    if head.is_empty() {
+        return Err("Input file is empty".into());
+    }
+    let codec = CODECS.iter().find(|codec| codec.matches(head, path));
+    // Plain input is parsed straight from the file's buffer
+    let mut buffered = match codec {
+        Some(codec) => BufReader::with_capacity(capacity, (codec.decode)(raw)?),
+        None => raw,
+    };
+    let compression = codec.map_or("none", |codec| codec.extension);
+
+    // Peek at the first byte
+    let first_byte = {
+        let buf = buffered.fill_buf()?;
+        if buf.is_empty() {
+            return Err("Input file is empty".into());
+        }
+        buf[0]
+    };
+
+    // Decide format by first byte
+    if first_byte == b'>' {
+        debug!(path = %path.display(), compression, format = "FASTA", "detected input format");
+        Ok(RecordReader::fasta(buffered))
+    } else if first_byte == b'@' {
+        debug!(path = %path.display(), compression, format = "FASTQ", "detected input format");
+        Ok(RecordReader::fastq(buffered))
+    } else {
+        Err(format!("Unknown file format: expected '>' or '@', got '{}'", first_byte as char).into())
+    }
+}
+
+/// Two inputs holding the two mates of each pair in the same order, as Illumina
+/// writes them (R1 and R2), read in lockstep
+pub struct PairedReader {
+    readers: [FileRecordReader; 2],
+    paths: [PathBuf; 2],
+    /// Pairs read so far
+    pairs: u64,
+}
+
+impl PairedReader {
+    pub fn open(first: &Path, second: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(PairedReader::new([open_reader(first)?, open_reader(second)?], [first, second]))
+    }
+
+    /// Pair up readers already opened, e.g. with [`open_mapped`], on the inputs at `paths`
+    pub fn new(readers: [FileRecordReader; 2], paths: [&Path; 2]) -> Self {
+        PairedReader { readers, paths: paths.map(Path::to_path_buf), pairs: 0 }
+    }
+
+    /// Next pair of mates, with their sequences uppercased unless `case_sensitive` is
+    /// set. A mate failing to parse yields a [`RecordError`] naming its input, even when
+    /// the other input has ended, and an input running out before the other a
+    /// [`MateCountError`].
+    pub fn next_pair(
+        &mut self,
+        case_sensitive: bool,
+    ) -> Option<Result<(SeqRecord, SeqRecord), Box<dyn std::error::Error + Send + Sync>>> {
+        let [first, second] = &mut self.readers;
+        let pair = match (first.next_record(case_sensitive), second.next_record(case_sensitive)) {
+            (None, None) => return None,
+            (Some(first), Some(second)) => (first, second),
+            // Only a mate that parsed shows the inputs differ in length
+            (Some(Err(e)), None) => return Some(Err(RecordError::new(&self.paths[0], self.pairs + 1, e).into())),
+            (None, Some(Err(e))) => return Some(Err(RecordError::new(&self.paths[1], self.pairs + 1, e).into())),
+            (Some(Ok(_)), None) => return Some(Err(self.count_mismatch(1).into())),
+            (None, Some(Ok(_))) => return Some(Err(self.count_mismatch(0).into())),
+        };
+        self.pairs += 1;
+        let record = self.pairs;
+        let located = |mate: usize| {
+            let path = &self.paths[mate];
+            move |e| -> Box<dyn std::error::Error + Send + Sync> { RecordError::new(path, record, e).into() }
+        };
+        Some(pair.0.map_err(located(0)).and_then(|first| Ok((first, pair.1.map_err(located(1))?))))
+    }
+
+    fn count_mismatch(&self, ended: usize) -> MateCountError {
+        MateCountError {
+            ended: self.paths[ended].clone(),
+            longer: self.paths[1 - ended].clone(),
+            records: self.pairs,
+        }
+    }
+}
+
+/// One input of a pair ended before the other
+pub struct MateCountError {
+    pub ended: PathBuf,
+    pub longer: PathBuf,
+    /// Records of `ended`, all of them paired
+    pub records: u64,
+}
+
+impl fmt::Display for MateCountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ends after {} records but {} holds more: paired inputs must hold the same reads in the same order",
+            self.ended.display(),
+            self.records,
+            self.longer.display()
+        )
+    }
+}
+
+impl fmt::Debug for MateCountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for MateCountError {}
+
+/// Like [`open_reader`], but an uncompressed regular file is memory-mapped and parsed in
+/// place, saving the read calls and buffer copies. Compressed files and standard input
+/// are read as [`open_reader`] reads them. The file must not change while it is read.
+pub fn open_mapped(path: &Path) -> Result<FileRecordReader, Box<dyn std::error::Error + Send + Sync>> {
+    open_mapped_with_capacity(path, DEFAULT_BUFFER_SIZE)
+}
+
+/// [`open_mapped`], reading files it cannot map with buffers of `capacity` bytes
+pub fn open_mapped_with_capacity(path: &Path, capacity: usize) -> Result<FileRecordReader, Box<dyn std::error::Error + Send + Sync>> {
+    if path == Path::new(STDIN_PATH) {
+        return open_reader_with_capacity(path, capacity);
+    }
+    let file = File::open(path)?;
+    if !file.metadata()?.is_file() {
+        return open_reader_with_capacity(path, capacity);
+    }
+    let mut head = [0u8; 4];
+    let len = (&file).take(head.len() as u64).read(&mut head)?;
+    let head = &head[..len];
+    if head.is_empty() {
+        return Err("Input file is empty".into());
+    }
+    if CODECS.iter().any(|codec| codec.matches(head, path)) {
+        return open_reader_with_capacity(path, capacity);
+    }
+    let fastq = match head[0] {
+        b'>' => false,
+        b'@' => true,
+        first_byte => {
+            return Err(format!("Unknown file format: expected '>' or '@', got '{}'", first_byte as char).into());
+        }
+    };
+    let format = if fastq { "FASTQ" } else { "FASTA" };
+    debug!(path = %path.display(), compression = "none", format, "detected input format, memory-mapped");
+    Ok(RecordReader::Mapped(MappedRecords::new(&file, fastq)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three records, the middle one with 28 bases but only 4 quality scores
+    const MISMATCHED: &[u8] = b"@r1\nACGTACGTACGTACGTACGTACGT\n+\nIIIIIIIIIIIIIIIIIIIIIIII\n\
+        @r2\nACGTACGTACGTACGTACGTACGTAAAA\n+\nIIII\n\
+        @r3\nTTTTACGTACGTACGTACGTACGT\n+\nIIIIIIIIIIIIIIIIIIIIIIII\n";
+
+    fn check_mismatch<R: Read>(mut reader: RecordReader<R>) {
+        let first = reader.next_record(false).unwrap().unwrap();
+        assert_eq!(first.seq, b"ACGTACGTACGTACGTACGTACGT");
+        let error = reader.next_record(false).unwrap().err().expect("record 2 is corrupt").to_string();
+        assert!(error.contains("28 bases") && error.contains("4 scores"), "{}", error);
+        let third = reader.next_record(false).unwrap().unwrap();
+        assert_eq!(third.seq, b"TTTTACGTACGTACGTACGTACGT");
+        assert_eq!(third.qual.map(|qual| qual.len()), Some(24));
+        assert!(reader.next_record(false).is_none());
+    }
+
+    #[test]
+    fn quality_length_mismatch_is_an_error_when_buffered() {
+        check_mismatch(RecordReader::fastq(BufReader::new(MISMATCHED)));
+    }
+
+    #[test]
+    fn quality_length_mismatch_is_an_error_when_mapped() {
+        let path = std::env::temp_dir().join(format!("mismatched-{}.fq", std::process::id()));
+        std::fs::write(&path, MISMATCHED).unwrap();
+        let reader = open_mapped(&path).unwrap();
+        assert!(matches!(reader, RecordReader::Mapped(_)));
+        check_mismatch(reader);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_last_mate_failing_to_parse_is_reported_over_the_count_mismatch() {
+        let fastq = |bytes: &'static [u8]| -> FileRecordReader { RecordReader::fastq(BufReader::new(Box::new(bytes))) };
+        // R1's third record is R2's missing one, but it is corrupt
+        let first = b"@r1\nACGT\n+\nIIII\n@r2\nACGT\n+\nIIII\n@r3\nACGTACGT\n+\nII\n";
+        let second = b"@r1\nTTTT\n+\nIIII\n@r2\nTTTT\n+\nIIII\n";
+        let paths = [Path::new("r1.fq"), Path::new("r2.fq")];
+        let mut reader = PairedReader::new([fastq(first), fastq(second)], paths);
+        assert!(reader.next_pair(false).unwrap().is_ok());
+        assert!(reader.next_pair(false).unwrap().is_ok());
+        let error = reader.next_pair(false).unwrap().err().expect("r1.fq's third record is corrupt");
+        let error = error.downcast::<RecordError>().expect("a parse error, not a count mismatch");
+        assert_eq!((error.path.as_path(), error.record), (Path::new("r1.fq"), 3));
+        assert!(reader.next_pair(false).is_none());
+
+        // A mate that parsed still shows the inputs differ in length
+        let mut reader = PairedReader::new([fastq(second), fastq(&second[..16])], paths);
+        assert!(reader.next_pair(false).unwrap().is_ok());
+        assert!(reader.next_pair(false).unwrap().err().expect("r2.fq ends first").is::<MateCountError>());
+    }
+}
@@ -0,0 +1,86 @@
+//! FASTA/FASTQ records parsed straight from a memory-mapped, uncompressed input
+
+use super::SeqRecord;
+use memchr::memchr;
+use memmap2::Mmap;
+use std::fs::File;
+
+/// Records of a mapped file, read without buffering or per-line allocations
+pub struct MappedRecords {
+    map: Mmap,
+    /// Start of the next unread line
+    pos: usize,
+    fastq: bool,
+}
+
+impl MappedRecords {
+    /// Map `file`, whose first byte has told FASTQ (`@`) from FASTA (`>`). The file must
+    /// not be truncated or rewritten while it is mapped.
+    pub(super) fn new(file: &File, fastq: bool) -> std::io::Result<Self> {
+        // SAFETY: the map is only read, and inputs are not expected to change while counted
+        let map = unsafe { Mmap::map(file)? };
+        #[cfg(unix)]
+        map.advise(memmap2::Advice::Sequential)?;
+        Ok(MappedRecords { map, pos: 0, fastq })
+    }
+
+    /// The next line, without its line break, or None at the end of the file
+    fn line(&mut self) -> Option<&[u8]> {
+        let rest = self.map.get(self.pos..).filter(|rest| !rest.is_empty())?;
+        let len = memchr(b'\n', rest).unwrap_or(rest.len());
+        self.pos += (len + 1).min(rest.len());
+        let line = &rest[..len];
+        Some(line.strip_suffix(b"\r").unwrap_or(line))
+    }
+
+    /// First byte of the next line, if any
+    fn peek(&self) -> Option<u8> {
+        self.map.get(self.pos).copied()
+    }
+
+    /// Parse the next record into `record`, reusing its buffers
+    pub(super) fn read_into(&mut self, record: &mut SeqRecord) -> Option<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
+        // Blank lines between records are skipped
+        while self.peek().is_some_and(|b| b == b'\n' || b == b'\r') {
+            self.line();
+        }
+        record.seq.clear();
+        if self.fastq { self.next_fastq(record) } else { self.next_fasta(record) }
+    }
+
+    fn next_fasta(&mut self, record: &mut SeqRecord) -> Option<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
+        let header = self.line()?;
+        if !header.starts_with(b">") {
+            return Some(Err("expected '>' at record start".into()));
+        }
+        while self.peek().is_some_and(|b| b != b'>') {
+            let line = self.line().expect("peeked a line");
+            record.seq.extend_from_slice(line);
+        }
+        record.qual = None;
+        Some(Ok(()))
+    }
+
+    fn next_fastq(&mut self, record: &mut SeqRecord) -> Option<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
+        let header = self.line()?;
+        if !header.starts_with(b"@") {
+            return Some(Err("expected '@' at record start".into()));
+        }
+        let Some(seq) = self.line() else {
+            return Some(Err("incomplete record: no sequence line".into()));
+        };
+        record.seq.extend_from_slice(seq);
+        match self.line() {
+            Some(separator) if separator.starts_with(b"+") => {}
+            Some(_) => return Some(Err("expected '+' separator line".into())),
+            None => return Some(Err("incomplete record: no '+' separator line".into())),
+        }
+        let Some(qual) = self.line() else {
+            return Some(Err("incomplete record: no quality line".into()));
+        };
+        let buffer = record.qual.get_or_insert_with(Vec::new);
+        buffer.clear();
+        buffer.extend_from_slice(qual);
+        Some(Ok(()))
+    }
+}
@@ -0,0 +1,194 @@
+//! Growth and acceleration of the k-mer curve between sampling points, and where it levels off
+
+use std::collections::VecDeque;
+
+/// Default number of recent samples averaged into the growth and acceleration trends
+pub const DEFAULT_HISTORY_WINDOW: usize = 10;
+
+/// Change in the k-mer count at one sampling point
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sample {
+    /// K-mers counted at this point
+    pub kmers: u64,
+    /// New k-mers since the previous sample
+    pub growth: i64,
+    /// Change in growth since the previous sample (0 until two growths are known)
+    pub acceleration: i64,
+    /// Mean growth over the history window
+    pub avg_growth: f32,
+    /// Mean acceleration over the history window
+    pub avg_accel: f32,
+}
+
+/// Follows how fast a k-mer count grows as reads are added, to tell when it saturates.
+///
+/// Growth is measured per sample, so its scale depends on how many reads lie
+/// between two calls to [`record`](Self::record).
+#[derive(Clone, Debug)]
+pub struct SaturationTracker {
+    prev_kmers: u64,
+    window: usize,
+    growth_history: VecDeque<i64>,
+    accel_history: VecDeque<i64>,
+    last: Sample,
+}
+
+impl SaturationTracker {
+    /// Start from `kmers` already counted, e.g. 0 or the size of a loaded snapshot
+    pub fn new(kmers: u64) -> Self {
+        SaturationTracker {
+            prev_kmers: kmers,
+            window: DEFAULT_HISTORY_WINDOW,
+            growth_history: VecDeque::with_capacity(DEFAULT_HISTORY_WINDOW + 1),
+            accel_history: VecDeque::with_capacity(DEFAULT_HISTORY_WINDOW + 1),
+            last: Sample { kmers, ..Sample::default() },
+        }
+    }
+
+    /// Average growth and acceleration over the last `window` samples instead of
+    /// [`DEFAULT_HISTORY_WINDOW`]. Must be called before the first sample.
+    pub fn set_history_window(&mut self, window: usize) {
+        assert!(window >= 1, "history window must be at least 1");
+        debug_assert!(self.growth_history.is_empty(), "history window changed after sampling started");
+        self.window = window;
+    }
+
+    /// Samples averaged into the growth and acceleration trends
+    pub fn history_window(&self) -> usize {
+        self.window
+    }
+
+    /// Record the k-mer count at the next sampling point
+    pub fn record(&mut self, kmers: u64) -> Sample {
+        let growth = kmers as i64 - self.prev_kmers as i64;
+        self.prev_kmers = kmers;
+
+        // Acceleration needs two growth values
+        let mut acceleration = 0;
+        if let Some(&before) = self.growth_history.back() {
+            acceleration = growth - before;
+            push_bounded(&mut self.accel_history, acceleration, self.window);
+        }
+        push_bounded(&mut self.growth_history, growth, self.window);
+
+        self.last = Sample {
+            kmers,
+            growth,
+            acceleration,
+            avg_growth: mean(&self.growth_history),
+            avg_accel: mean(&self.accel_history),
+        };
+        self.last
+    }
+
+    /// The most recent sample, or the starting count if nothing was recorded yet
+    pub fn last(&self) -> Sample {
+        self.last
+    }
+}
+
+fn push_bounded(history: &mut VecDeque<i64>, value: i64, window: usize) {
+    history.push_back(value);
+    if history.len() > window {
+        history.pop_front();
+    }
+}
+
+fn mean(values: &VecDeque<i64>) -> f32 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<i64>() as f32 / values.len() as f32
+    }
+}
+
+/// Log-spaced half-saturation points tried by [`SaturationFit::fit`] before refining the best
+const FIT_GRID: usize = 240;
+/// Half-saturation points are searched from `1e-3` to `1e2` times the last read count
+const FIT_RANGE: (f64, f64) = (-3.0, 2.0);
+
+/// Michaelis-Menten model of a k-mer curve, `kmers = start + growth * reads / (half + reads)`:
+/// the k-mers approach `start + growth` and half the growth is reached after `half` reads
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SaturationFit {
+    /// K-mers counted before the first read, e.g. from a loaded snapshot
+    pub start: u64,
+    /// New k-mers the input adds at most
+    pub growth: f64,
+    /// Reads after which half of `growth` is reached
+    pub half: f64,
+}
+
+impl SaturationFit {
+    /// Least-squares fit to `(reads, kmers)` samples of a curve that starts at `start`
+    /// k-mers. None with fewer than 3 samples, a curve that does not grow, or one that
+    /// still grows too steadily to tell where it levels off.
+    pub fn fit(start: u64, points: &[(u64, u64)]) -> Option<Self> {
+        if points.len() < 3 {
+            return None;
+        }
+        let points: Vec<(f64, f64)> =
+            points.iter().map(|&(reads, kmers)| (reads as f64, kmers as f64 - start as f64)).collect();
+        let last = points.iter().map(|&(reads, _)| reads).fold(0.0, f64::max);
+        if last <= 0.0 {
+            return None;
+        }
+
+        // For a fixed half-saturation point the best growth has a closed form, which
+        // leaves a one-dimensional search: on a coarse log grid, then golden section
+        let growth_at = |half: f64| {
+            let (mut fy, mut ff) = (0.0, 0.0);
+            for &(reads, kmers) in &points {
+                let f = reads / (half + reads);
+                fy += f * kmers;
+                ff += f * f;
+            }
+            if ff > 0.0 { fy / ff } else { 0.0 }
+        };
+        let error_at = |log_half: f64| {
+            let half = log_half.exp();
+            let growth = growth_at(half);
+            points.iter().map(|&(reads, kmers)| (kmers - growth * reads / (half + reads)).powi(2)).sum::<f64>()
+        };
+
+        let (low, high) = (last.ln() + FIT_RANGE.0 * 10f64.ln(), last.ln() + FIT_RANGE.1 * 10f64.ln());
+        let step = (high - low) / FIT_GRID as f64;
+        let errors: Vec<f64> = (0..=FIT_GRID).map(|i| error_at(low + step * i as f64)).collect();
+        let best = (0..=FIT_GRID).min_by(|&a, &b| errors[a].total_cmp(&errors[b]))?;
+        // A minimum at the far end means the curve is still close to a straight line
+        if best == FIT_GRID {
+            return None;
+        }
+
+        let (mut a, mut b) = (low + step * best.saturating_sub(1) as f64, low + step * (best + 1) as f64);
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        for _ in 0..60 {
+            let (c, d) = (b - ratio * (b - a), a + ratio * (b - a));
+            if error_at(c) < error_at(d) {
+                b = d;
+            } else {
+                a = c;
+            }
+        }
+        let half = ((a + b) / 2.0).exp();
+        let growth = growth_at(half);
+        (growth > 0.0).then_some(SaturationFit { start, growth, half })
+    }
+
+    /// K-mers the input approaches
+    pub fn asymptote(&self) -> f64 {
+        self.start as f64 + self.growth
+    }
+
+    /// Modelled k-mers after `reads` reads
+    pub fn kmers_at(&self, reads: f64) -> f64 {
+        self.start as f64 + self.growth * reads / (self.half + reads)
+    }
+
+    /// Reads after which the curve reaches `fraction` (in (0, 1)) of its asymptote;
+    /// 0 when the starting k-mers already make up that share
+    pub fn reads_for(&self, fraction: f64) -> f64 {
+        let share = (fraction * self.asymptote() - self.start as f64) / self.growth;
+        if share <= 0.0 { 0.0 } else { self.half * share / (1.0 - share) }
+    }
+}
@@ -0,0 +1,165 @@
+//! Base composition and read-length summary of the reads seen
+
+use std::collections::BTreeMap;
+
+/// Running base and read-length statistics, cheap enough to update for every read
+#[derive(Clone, Debug)]
+pub struct SeqStats {
+    /// Occurrences of every byte value
+    bytes: [u64; 256],
+    /// Number of reads of each length
+    lengths: BTreeMap<usize, u64>,
+    reads: u64,
+}
+
+impl Default for SeqStats {
+    fn default() -> Self {
+        SeqStats { bytes: [0; 256], lengths: BTreeMap::new(), reads: 0 }
+    }
+}
+
+impl SeqStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Account for one read
+    pub fn add(&mut self, seq: &[u8]) {
+        for &base in seq {
+            self.bytes[base as usize] += 1;
+        }
+        *self.lengths.entry(seq.len()).or_insert(0) += 1;
+        self.reads += 1;
+    }
+
+    pub fn reads(&self) -> u64 {
+        self.reads
+    }
+
+    /// Total bases of all reads
+    pub fn bases(&self) -> u64 {
+        self.bytes.iter().sum()
+    }
+
+    /// Occurrences of `base` in either case
+    pub fn count(&self, base: u8) -> u64 {
+        self.bytes[base.to_ascii_uppercase() as usize] + self.bytes[base.to_ascii_lowercase() as usize]
+    }
+
+    /// Bases other than A/C/G/T/U/N, e.g. IUPAC ambiguity codes
+    pub fn other(&self) -> u64 {
+        self.bases() - b"ACGTUN".iter().map(|&base| self.count(base)).sum::<u64>()
+    }
+
+    /// Share of G and C among the unambiguous bases (A/C/G/T/U), 0 without any
+    pub fn gc_fraction(&self) -> f64 {
+        let gc = self.count(b'G') + self.count(b'C');
+        let acgt = gc + self.count(b'A') + self.count(b'T') + self.count(b'U');
+        if acgt == 0 { 0.0 } else { gc as f64 / acgt as f64 }
+    }
+
+    pub fn mean_length(&self) -> f64 {
+        if self.reads == 0 { 0.0 } else { self.bases() as f64 / self.reads as f64 }
+    }
+
+    /// Median read length (the lower median for an even number of reads), 0 without reads
+    pub fn median_length(&self) -> usize {
+        let middle = self.reads.div_ceil(2);
+        let mut seen = 0;
+        for (&length, &reads) in &self.lengths {
+            seen += reads;
+            if seen >= middle {
+                return length;
+            }
+        }
+        0
+    }
+
+    /// Read-length N50: the length such that reads at least this long hold half the bases
+    pub fn n50(&self) -> usize {
+        let half = self.bases().div_ceil(2);
+        let mut seen = 0;
+        for (&length, &reads) in self.lengths.iter().rev() {
+            seen += length as u64 * reads;
+            if seen >= half {
+                return length;
+            }
+        }
+        0
+    }
+}
+
+/// Bytes that are plain nucleotides, in either case: A, C, G, T and U
+const NUCLEOTIDES: [bool; 256] = {
+    let mut table = [false; 256];
+    let bases = b"ACGTUacgtu";
+    let mut i = 0;
+    while i < bases.len() {
+        table[bases[i] as usize] = true;
+        i += 1;
+    }
+    table
+};
+
+/// IUPAC ambiguity codes, N included, in either case
+const AMBIGUITY_CODES: &[u8] = b"NRYSWKMBDHVnryswkmbdhv";
+
+/// Reads holding anything but A/C/G/T/U, and how often each such byte occurs, to
+/// tell ordinary `N`s from the dots, dashes or whitespace of a malformed file
+#[derive(Clone, Debug)]
+pub struct NonAcgt {
+    /// Occurrences of every byte other than a plain nucleotide
+    bytes: [u64; 256],
+    /// Reads holding at least one of them
+    reads: u64,
+    /// All reads looked at
+    checked: u64,
+}
+
+impl Default for NonAcgt {
+    fn default() -> Self {
+        NonAcgt { bytes: [0; 256], reads: 0, checked: 0 }
+    }
+}
+
+impl NonAcgt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Account for one read; clean reads are only checked, not tallied byte by byte
+    pub fn add(&mut self, seq: &[u8]) {
+        self.checked += 1;
+        if seq.iter().all(|&base| NUCLEOTIDES[base as usize]) {
+            return;
+        }
+        self.reads += 1;
+        for &base in seq.iter().filter(|&&base| !NUCLEOTIDES[base as usize]) {
+            self.bytes[base as usize] += 1;
+        }
+    }
+
+    /// Reads holding a byte other than A/C/G/T/U
+    pub fn reads(&self) -> u64 {
+        self.reads
+    }
+
+    /// Reads looked at, clean or not
+    pub fn checked(&self) -> u64 {
+        self.checked
+    }
+
+    /// Every byte other than A/C/G/T/U seen, with its occurrences, most frequent first
+    pub fn counts(&self) -> Vec<(u8, u64)> {
+        let mut counts: Vec<(u8, u64)> =
+            (0..=u8::MAX).map(|byte| (byte, self.bytes[byte as usize])).filter(|&(_, n)| n > 0).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// The bytes seen that are not IUPAC nucleotide codes either, such as `.` or
+    /// whitespace, a sign of a malformed file rather than of uncalled bases
+    pub fn unexpected(&self) -> Vec<u8> {
+        self.counts().into_iter().map(|(byte, _)| byte).filter(|byte| !AMBIGUITY_CODES.contains(byte)).collect()
+    }
+}
@@ -0,0 +1,347 @@
+//! Probabilistic summaries of k-mer streams for inputs too large to count exactly.
+
+use std::collections::BTreeSet;
+
+/// Finalizer of MurmurHash3, spreading the bits of a weak hash (e.g. FxHash)
+/// well enough for the sketches below, which read specific bit ranges
+pub fn mix64(mut x: u64) -> u64 {
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    x ^= x >> 33;
+    x
+}
+
+/// HyperLogLog distinct-count estimator with `2^precision` one-byte registers
+#[derive(Clone)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    /// Smallest and largest supported precision
+    pub const PRECISION_RANGE: std::ops::RangeInclusive<u8> = 4..=18;
+
+    pub fn new(precision: u8) -> Self {
+        assert!(
+            Self::PRECISION_RANGE.contains(&precision),
+            "HyperLogLog precision must be in {:?}",
+            Self::PRECISION_RANGE
+        );
+        HyperLogLog {
+            precision,
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// Add an already well-mixed 64-bit hash
+    pub fn insert_hash(&mut self, hash: u64) {
+        let p = self.precision;
+        let index = (hash >> (64 - p)) as usize;
+        // Guard bit keeps the rank bounded when the remaining bits are all zero
+        let rest = (hash << p) | (1 << (p - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    /// Fold another sketch of the same precision into this one
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        assert_eq!(self.precision, other.precision, "cannot merge HyperLogLogs of different precision");
+        for (mine, theirs) in self.registers.iter_mut().zip(&other.registers) {
+            *mine = (*mine).max(*theirs);
+        }
+    }
+
+    /// Estimated number of distinct hashes inserted
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = alpha * m * m / sum;
+
+        // Linear counting is more accurate while many registers are still empty
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+
+    /// Standard relative error of the estimate, `1.04 / sqrt(m)`
+    pub fn relative_error(&self) -> f64 {
+        1.04 / (self.registers.len() as f64).sqrt()
+    }
+
+    /// Memory used by the registers, in bytes
+    pub fn memory_bytes(&self) -> usize {
+        self.registers.len()
+    }
+}
+
+/// Count-Min sketch with conservative update: every key's estimate is at
+/// least its true count, and over-counts only come from hash collisions
+#[derive(Clone)]
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    counters: Vec<u32>,
+    total: u64,
+}
+
+impl CountMinSketch {
+    pub fn new(width: usize, depth: usize) -> Self {
+        assert!(width > 0 && depth > 0, "Count-Min sketch needs a non-zero width and depth");
+        CountMinSketch {
+            width,
+            depth,
+            counters: vec![0; width * depth],
+            total: 0,
+        }
+    }
+
+    /// Widest sketch of `depth` rows fitting in `bytes`
+    pub fn with_memory(bytes: usize, depth: usize) -> Self {
+        let width = (bytes / (depth * std::mem::size_of::<u32>())).max(1);
+        Self::new(width, depth)
+    }
+
+    /// Counter index in each row, from double hashing of one 64-bit hash
+    fn cells(&self, hash: u64) -> impl Iterator<Item = usize> + use<> {
+        let (width, step) = (self.width, mix64(hash) | 1);
+        (0..self.depth).map(move |row| row * width + (hash.wrapping_add((row as u64).wrapping_mul(step)) % width as u64) as usize)
+    }
+
+    /// Count one occurrence of `hash`, returning its estimate before the update
+    pub fn insert_hash(&mut self, hash: u64) -> u32 {
+        let previous = self.estimate_hash(hash);
+        let updated = previous.saturating_add(1);
+        for cell in self.cells(hash) {
+            if self.counters[cell] < updated {
+                self.counters[cell] = updated;
+            }
+        }
+        self.total += 1;
+        previous
+    }
+
+    /// Estimated count of `hash`, never below its true count
+    pub fn estimate_hash(&self, hash: u64) -> u32 {
+        self.cells(hash).map(|cell| self.counters[cell]).min().unwrap_or(0)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Total number of insertions
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Memory used by the counters, in bytes
+    pub fn memory_bytes(&self) -> usize {
+        self.counters.len() * std::mem::size_of::<u32>()
+    }
+
+    /// Bound on the over-count of any single estimate, `e / width * total`,
+    /// which holds with probability `confidence()`
+    pub fn error_bound(&self) -> f64 {
+        std::f64::consts::E / self.width as f64 * self.total as f64
+    }
+
+    /// Probability that an estimate stays within `error_bound()`, `1 - e^-depth`
+    pub fn confidence(&self) -> f64 {
+        1.0 - (-(self.depth as f64)).exp()
+    }
+}
+
+/// Bounded table of the most frequent keys seen so far, by their estimated count
+#[derive(Clone)]
+pub struct HeavyHitters {
+    capacity: usize,
+    entries: Vec<HeavyHitter>,
+}
+
+/// One heavy hitter: the key's hash, its bytes and its latest estimated count
+#[derive(Clone, Debug)]
+pub struct HeavyHitter {
+    pub hash: u64,
+    pub kmer: Vec<u8>,
+    pub count: u32,
+}
+
+impl HeavyHitters {
+    pub fn new(capacity: usize) -> Self {
+        HeavyHitters {
+            capacity,
+            entries: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Offer a key with its estimated count; `kmer` is only called when the
+    /// key enters the table, so callers can defer building its bytes
+    pub fn offer(&mut self, hash: u64, count: u32, kmer: impl FnOnce() -> Vec<u8>) {
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.hash == hash) {
+            entry.count = entry.count.max(count);
+            return;
+        }
+        if self.entries.len() < self.capacity {
+            self.entries.push(HeavyHitter { hash, kmer: kmer(), count });
+            return;
+        }
+        match self.entries.iter_mut().min_by_key(|e| e.count) {
+            Some(lowest) if count > lowest.count => *lowest = HeavyHitter { hash, kmer: kmer(), count },
+            _ => {}
+        }
+    }
+
+    /// Entries ordered from most to least frequent
+    pub fn sorted(&self) -> Vec<HeavyHitter> {
+        let mut entries = self.entries.clone();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.count));
+        entries
+    }
+
+    /// Smallest count that could still enter a full table
+    pub fn min_count(&self) -> u32 {
+        if self.entries.len() < self.capacity {
+            return 0;
+        }
+        self.entries.iter().map(|e| e.count).min().unwrap_or(0)
+    }
+
+    /// Memory used by the entries and their k-mer bytes
+    pub fn memory_bytes(&self) -> usize {
+        self.capacity * std::mem::size_of::<HeavyHitter>() + self.entries.iter().map(|e| e.kmer.capacity()).sum::<usize>()
+    }
+}
+
+/// Seed of the MinHash hash, the one Mash and sourmash use by default
+pub const MINHASH_SEED: u32 = 42;
+
+/// First 64 bits of MurmurHash3_x64_128 (Austin Appleby's reference
+/// implementation) of `data`. This is a stable hash: it gives the same values
+/// on every platform and in every release, unlike the hashes of the maps.
+pub fn murmur3_64(data: &[u8], seed: u32) -> u64 {
+    murmur3_x64_128(data, seed)[0]
+}
+
+fn murmur3_x64_128(data: &[u8], seed: u32) -> [u64; 2] {
+    const C1: u64 = 0x87c3_7b91_1142_53d5;
+    const C2: u64 = 0x4cf5_ad43_2745_937f;
+    let mix_k1 = |k1: u64| k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+    let mix_k2 = |k2: u64| k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+    let lane = |bytes: &[u8]| bytes.iter().rev().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+
+    let (mut h1, mut h2) = (seed as u64, seed as u64);
+    let mut blocks = data.chunks_exact(16);
+    for block in &mut blocks {
+        h1 ^= mix_k1(lane(&block[..8]));
+        h1 = h1.rotate_left(27).wrapping_add(h2).wrapping_mul(5).wrapping_add(0x52dc_e729);
+        h2 ^= mix_k2(lane(&block[8..]));
+        h2 = h2.rotate_left(31).wrapping_add(h1).wrapping_mul(5).wrapping_add(0x3849_5ab5);
+    }
+    let tail = blocks.remainder();
+    if tail.len() > 8 {
+        h2 ^= mix_k2(lane(&tail[8..]));
+    }
+    if !tail.is_empty() {
+        h1 ^= mix_k1(lane(&tail[..tail.len().min(8)]));
+    }
+
+    h1 ^= data.len() as u64;
+    h2 ^= data.len() as u64;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = mix64(h1);
+    h2 = mix64(h2);
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    [h1, h2]
+}
+
+/// Bottom-k MinHash sketch: the `size` smallest distinct hashes seen
+#[derive(Clone, Debug)]
+pub struct MinHash {
+    size: usize,
+    hashes: BTreeSet<u64>,
+}
+
+impl MinHash {
+    pub fn new(size: usize) -> Self {
+        assert!(size >= 1, "a MinHash sketch keeps at least one hash");
+        MinHash { size, hashes: BTreeSet::new() }
+    }
+
+    /// Number of hashes kept once the sketch is full
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn insert_hash(&mut self, hash: u64) {
+        if self.hashes.len() < self.size {
+            self.hashes.insert(hash);
+        } else if self.hashes.last().is_some_and(|&largest| hash < largest) && self.hashes.insert(hash) {
+            self.hashes.pop_last();
+        }
+    }
+
+    /// Fold in a sketch of other data, as if its hashes had been inserted here
+    pub fn merge(&mut self, other: &MinHash) {
+        for &hash in &other.hashes {
+            self.insert_hash(hash);
+        }
+    }
+
+    /// Estimated Jaccard index of the two sketched sets: the share of the
+    /// smallest hashes of their union that both sketches hold
+    pub fn jaccard(&self, other: &MinHash) -> f64 {
+        let size = self.size.min(other.size);
+        let (mut a, mut b) = (self.hashes.iter().peekable(), other.hashes.iter().peekable());
+        let (mut union, mut shared) = (0, 0);
+        while union < size {
+            match (a.peek(), b.peek()) {
+                (Some(x), Some(y)) if x == y => {
+                    shared += 1;
+                    a.next();
+                    b.next();
+                }
+                (Some(x), Some(y)) if x < y => {
+                    a.next();
+                }
+                (Some(_), None) => {
+                    a.next();
+                }
+                (_, Some(_)) => {
+                    b.next();
+                }
+                (None, None) => break,
+            }
+            union += 1;
+        }
+        if union == 0 { 0.0 } else { shared as f64 / union as f64 }
+    }
+
+    /// The kept hashes in increasing order
+    pub fn hashes(&self) -> impl Iterator<Item = u64> + '_ {
+        self.hashes.iter().copied()
+    }
+}
@@ -0,0 +1,61 @@
+//! Genome properties read off the k-mer abundance spectrum
+
+mod svg;
+
+pub use svg::spectrum_svg;
+
+/// The coverage peak must be at least this many times higher than the valley
+/// before it, so noise in a falling tail is not mistaken for coverage
+const MIN_PEAK_PROMINENCE: u64 = 2;
+
+/// Share of all k-mer occurrences that must lie past the valley: genomic k-mers carry
+/// most occurrences unless the reads are mostly errors, or the peak is tail noise
+const MIN_GENOMIC_SHARE: f64 = 0.1;
+
+/// Naive genome size estimate from the main coverage peak of a k-mer spectrum, as
+/// in the GenomeScope tutorial: k-mers past the error valley over their peak depth
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GenomeEstimate {
+    /// Abundance of the first valley; less abundant k-mers are taken as sequencing errors
+    pub error_cutoff: u16,
+    /// Abundance of the main peak, the k-mer coverage depth
+    pub peak: u16,
+    /// Occurrences of the k-mers at or above the cutoff
+    pub kmers: u64,
+    /// `kmers / peak`, in bases
+    pub genome_size: u64,
+}
+
+/// Locate the coverage peak of `histogram` (`(abundance, distinct k-mers)` pairs in
+/// increasing abundance, as [`KmerCounter::histogram`](crate::KmerCounter::histogram)
+/// returns them with full counts). None when the spectrum has no clear peak past the
+/// error k-mers: it only falls, the highest point is the saturated last bin or barely
+/// rises above the valley, or hardly any k-mer occurrences lie past the valley.
+pub fn estimate_genome_size(histogram: &[(u16, u64)]) -> Option<GenomeEstimate> {
+    let &(max, _) = histogram.last()?;
+    let mut bins = vec![0u64; max as usize + 1];
+    for &(abundance, kmers) in histogram {
+        bins[abundance as usize] = kmers;
+    }
+
+    // Error k-mers fall off from abundance 1 until the first bin where the spectrum rises again
+    let valley = (1..bins.len() - 1).find(|&a| bins[a + 1] > bins[a])?;
+    // Counts stop at u16::MAX, so that bin gathers everything more abundant
+    let searched = if max == u16::MAX { &bins[..max as usize] } else { &bins[..] };
+    let peak = (valley..searched.len()).max_by_key(|&a| (searched[a], std::cmp::Reverse(a)))?;
+    if searched[peak] < bins[valley].max(1) * MIN_PEAK_PROMINENCE {
+        return None;
+    }
+
+    let occurrences = |from: usize| bins.iter().enumerate().skip(from).map(|(a, &n)| a as u64 * n).sum::<u64>();
+    let kmers = occurrences(valley);
+    if (kmers as f64) < occurrences(1) as f64 * MIN_GENOMIC_SHARE {
+        return None;
+    }
+    Some(GenomeEstimate {
+        error_cutoff: valley as u16,
+        peak: peak as u16,
+        kmers,
+        genome_size: kmers / peak as u64,
+    })
+}
@@ -0,0 +1,175 @@
+//! The k-mer spectrum drawn as a bar chart, in hand-written SVG
+
+use std::fmt::Write;
+
+use super::estimate_genome_size;
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 500.0;
+/// Room left of, right of, above and below the plot area, for the axes and their labels
+const MARGIN: (f64, f64, f64, f64) = (80.0, 20.0, 40.0, 60.0);
+
+/// Abundances shown past the coverage peak, as a multiple of it
+const PEAK_SPAN: u16 = 3;
+/// Without a peak, the chart goes as far as needed to show this share of all k-mer occurrences
+const SHOWN_OCCURRENCES: f64 = 0.99;
+/// Abundances shown at most, so a long tail does not squeeze the bars to nothing
+const MAX_ABUNDANCE: u16 = 1000;
+/// Headroom over the coverage peak: the error k-mers below the valley usually dwarf it,
+/// and are cut off at the top of the chart instead
+const PEAK_HEADROOM: f64 = 1.5;
+
+const ERROR_COLOR: &str = "#bbbbbb";
+const GENOMIC_COLOR: &str = "#4878a8";
+const PEAK_COLOR: &str = "#c0392b";
+
+/// Render `histogram` (`(abundance, distinct k-mers)` pairs in increasing abundance, as
+/// [`KmerCounter::histogram`](crate::KmerCounter::histogram) returns them) as an SVG bar
+/// chart of distinct k-mers by abundance, for k-mers of length `k`. When
+/// [`estimate_genome_size`] finds a coverage peak, it is marked with a dashed line, the
+/// error k-mers below the valley are greyed out and the y axis is scaled to the peak.
+pub fn spectrum_svg(histogram: &[(u16, u64)], k: usize) -> String {
+    let estimate = estimate_genome_size(histogram);
+    let max_abundance = match estimate {
+        Some(estimate) => estimate.peak.saturating_mul(PEAK_SPAN),
+        None => shown_abundance(histogram),
+    }
+    .clamp(10, MAX_ABUNDANCE);
+    let shown: Vec<(u16, u64)> = histogram.iter().copied().filter(|&(a, _)| a >= 1 && a <= max_abundance).collect();
+    let max_kmers = match estimate {
+        Some(estimate) => {
+            let peak = histogram.iter().find(|&&(a, _)| a == estimate.peak).map_or(0, |&(_, n)| n);
+            (peak as f64 * PEAK_HEADROOM) as u64
+        }
+        None => shown.iter().map(|&(_, n)| n).max().unwrap_or(0),
+    }
+    .max(1);
+
+    let (left, right, top, bottom) = MARGIN;
+    let (plot_width, plot_height) = (WIDTH - left - right, HEIGHT - top - bottom);
+    let bar_width = plot_width / max_abundance as f64;
+    // Left edge of the bar for an abundance, so its centre is at `x(a + 0.5)`
+    let x = |abundance: f64| left + (abundance - 1.0) * bar_width;
+    let y = |kmers: f64| top + plot_height * (1.0 - (kmers / max_kmers as f64).min(1.0));
+
+    let mut svg = String::new();
+    // Writing to a String cannot fail
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="sans-serif" font-size="12">"#
+    );
+    let _ = writeln!(svg, r#"<rect width="{WIDTH}" height="{HEIGHT}" fill="white"/>"#);
+    let _ = writeln!(svg, r#"<text x="{}" y="24" text-anchor="middle" font-size="16">k-mer spectrum (k = {})</text>"#, WIDTH / 2.0, k);
+
+    for &(abundance, kmers) in &shown {
+        let error = estimate.is_some_and(|estimate| abundance < estimate.error_cutoff);
+        let bar_top = y(kmers as f64);
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}"><title>{}x: {} k-mers</title></rect>"#,
+            x(abundance as f64),
+            bar_top,
+            bar_width,
+            top + plot_height - bar_top,
+            if error { ERROR_COLOR } else { GENOMIC_COLOR },
+            abundance,
+            kmers
+        );
+    }
+
+    // Axes, with ticks at round values
+    let axis_y = top + plot_height;
+    let _ = writeln!(
+        svg,
+        r#"<path d="M{left} {top} V{axis_y} H{}" fill="none" stroke="black"/>"#,
+        left + plot_width
+    );
+    for tick in ticks(max_abundance as u64).into_iter().filter(|&tick| tick > 0) {
+        // Centred on its bar
+        let tick_x = x(tick as f64 + 0.5);
+        let _ = writeln!(
+            svg,
+            r#"<line x1="{tick_x:.2}" y1="{axis_y}" x2="{tick_x:.2}" y2="{}" stroke="black"/><text x="{tick_x:.2}" y="{}" text-anchor="middle">{tick}</text>"#,
+            axis_y + 5.0,
+            axis_y + 18.0
+        );
+    }
+    for tick in ticks(max_kmers) {
+        let tick_y = y(tick as f64);
+        let _ = writeln!(
+            svg,
+            r#"<line x1="{}" y1="{tick_y:.2}" x2="{left}" y2="{tick_y:.2}" stroke="black"/><text x="{}" y="{:.2}" text-anchor="end">{}</text>"#,
+            left - 5.0,
+            left - 8.0,
+            tick_y + 4.0,
+            compact(tick)
+        );
+    }
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" text-anchor="middle">abundance (times a k-mer was seen)</text>"#,
+        left + plot_width / 2.0,
+        HEIGHT - 15.0
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text transform="translate(20 {}) rotate(-90)" text-anchor="middle">distinct k-mers</text>"#,
+        top + plot_height / 2.0
+    );
+
+    if let Some(estimate) = estimate {
+        let peak_x = x(estimate.peak as f64 + 0.5);
+        let _ = writeln!(
+            svg,
+            r#"<line x1="{peak_x:.2}" y1="{top}" x2="{peak_x:.2}" y2="{axis_y}" stroke="{PEAK_COLOR}" stroke-dasharray="6 4"/>"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.2}" y="{}" fill="{PEAK_COLOR}">coverage peak {}x, genome ~{} bp</text>"#,
+            peak_x + 6.0,
+            top + 16.0,
+            estimate.peak,
+            compact(estimate.genome_size)
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Smallest abundance up to which the k-mers hold `SHOWN_OCCURRENCES` of all occurrences
+fn shown_abundance(histogram: &[(u16, u64)]) -> u16 {
+    let total: u64 = histogram.iter().map(|&(a, n)| a as u64 * n).sum();
+    let mut covered = 0;
+    for &(abundance, kmers) in histogram {
+        covered += abundance as u64 * kmers;
+        if covered as f64 >= total as f64 * SHOWN_OCCURRENCES {
+            return abundance;
+        }
+    }
+    histogram.last().map_or(0, |&(a, _)| a)
+}
+
+/// Round tick values from 0 to `max`: steps of 1, 2 or 5 times a power of ten, about five of them
+fn ticks(max: u64) -> Vec<u64> {
+    let rough = (max as f64 / 5.0).max(1.0);
+    let power = 10f64.powi(rough.log10().floor() as i32);
+    let step = [1.0, 2.0, 5.0, 10.0].into_iter().map(|m| m * power).find(|&step| step >= rough).unwrap_or(power * 10.0);
+    let step = step as u64;
+    (0..=max / step).map(|i| i * step).collect()
+}
+
+/// `n` with a k/M/G suffix past a thousand
+fn compact(n: u64) -> String {
+    match n {
+        0..1_000 => n.to_string(),
+        1_000..1_000_000 => format!("{}k", trim(n as f64 / 1e3)),
+        1_000_000..1_000_000_000 => format!("{}M", trim(n as f64 / 1e6)),
+        _ => format!("{}G", trim(n as f64 / 1e9)),
+    }
+}
+
+/// One decimal, dropped when it is zero
+fn trim(value: f64) -> String {
+    let text = format!("{:.1}", value);
+    text.strip_suffix(".0").map(str::to_string).unwrap_or(text)
+}
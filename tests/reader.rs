@@ -0,0 +1,58 @@
+//! Compressed and concatenated inputs read through `open_reader`
+
+use std::path::{Path, PathBuf};
+
+use unique_kmers_evolution::open_reader;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+/// Every record of `path` as (sequence, quality), as read case-sensitively
+fn records(path: &Path) -> Vec<(Vec<u8>, Option<Vec<u8>>)> {
+    let mut reader = open_reader(path).unwrap();
+    let mut records = Vec::new();
+    while let Some(record) = reader.next_record(true) {
+        let record = record.unwrap();
+        records.push((record.seq, record.qual));
+    }
+    records
+}
+
+/// The error `open_reader` gives for a file holding only `bytes`, named `name`
+fn open_error(name: &str, bytes: &[u8]) -> String {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::write(&path, bytes).unwrap();
+    match open_reader(&path) {
+        Ok(mut reader) => match reader.next_record(true) {
+            Some(Err(e)) => e.to_string(),
+            _ => panic!("{} was read without error", name),
+        },
+        Err(e) => e.to_string(),
+    }
+}
+
+#[test]
+fn zstd_input_matches_the_plain_file() {
+    let plain = records(&fixture("reads.fq"));
+    assert_eq!(plain.len(), 4);
+    assert_eq!(records(&fixture("reads.fq.zst")), plain);
+}
+
+#[test]
+fn every_member_of_a_concatenated_gzip_is_read() {
+    // Two gzip members of two records each, as `cat a.fq.gz b.fq.gz` makes
+    let concatenated = records(&fixture("two_members.fq.gz"));
+    assert_eq!(concatenated.len(), 4);
+    assert_eq!(concatenated, records(&fixture("reads.fq")));
+}
+
+#[test]
+fn short_zst_streams_are_decoded_by_extension() {
+    // Three bytes cannot hold the four-byte magic, so only the extension tells zstd apart
+    let truncated_magic = [0x28, 0xb5, 0x2f];
+    let as_zstd = open_error("short.fq.zst", &truncated_magic);
+    assert!(as_zstd.contains("incomplete frame"), "{}", as_zstd);
+    let as_plain = open_error("short.fq", &truncated_magic);
+    assert!(as_plain.contains("Unknown file format"), "{}", as_plain);
+}